@@ -0,0 +1,862 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use crate::graph::DepGraph;
+
+type Longest<'a> = HashMap<&'a str, (usize, Option<&'a str>)>;
+
+fn longest_from<'a>(
+    node: &'a str,
+    adjacency: &HashMap<&'a str, Vec<&'a str>>,
+    longest: &mut Longest<'a>,
+    stack: &mut HashSet<&'a str>,
+) -> usize {
+    if let Some((length, _)) = longest.get(node) {
+        return *length;
+    }
+
+    stack.insert(node);
+    let mut best = (1, None);
+    for &child in adjacency.get(node).into_iter().flatten() {
+        // back edges would make the chain infinite, a cycle only counts once
+        if stack.contains(child) {
+            continue;
+        }
+
+        let length = longest_from(child, adjacency, longest, stack) + 1;
+        if length > best.0 {
+            best = (length, Some(child));
+        }
+    }
+    stack.remove(node);
+
+    longest.insert(node, best);
+    best.0
+}
+
+pub fn critical_path(graph: &DepGraph) -> Vec<String> {
+    let adjacency = graph.adjacency();
+    let mut longest = HashMap::new();
+    longest_from(&graph.root, &adjacency, &mut longest, &mut HashSet::new());
+
+    let mut path = vec![graph.root.clone()];
+    let mut seen = HashSet::from([graph.root.as_str()]);
+    let mut current = graph.root.as_str();
+    while let Some((_, Some(next))) = longest.get(current) {
+        if !seen.insert(next) {
+            break;
+        }
+        path.push(next.to_string());
+        current = next;
+    }
+
+    path
+}
+
+pub fn in_degrees(graph: &DepGraph) -> HashMap<&str, usize> {
+    let mut in_degrees: HashMap<&str, usize> = HashMap::new();
+    for &child in graph.adjacency().values().flatten() {
+        *in_degrees.entry(child).or_default() += 1;
+    }
+    in_degrees
+}
+
+pub struct Centrality {
+    pub package: String,
+    pub in_degree: usize,
+    pub betweenness: f64,
+}
+
+pub fn centrality(graph: &DepGraph) -> Vec<Centrality> {
+    let packages = graph.packages();
+    let index: HashMap<&str, usize> = packages.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let adjacency: Vec<Vec<usize>> = {
+        let adjacency = graph.adjacency();
+        packages
+            .iter()
+            .map(|p| {
+                adjacency
+                    .get(p)
+                    .into_iter()
+                    .flatten()
+                    .map(|c| index[c])
+                    .collect()
+            })
+            .collect()
+    };
+
+    let mut in_degree = vec![0; packages.len()];
+    for children in &adjacency {
+        for &c in children {
+            in_degree[c] += 1;
+        }
+    }
+
+    // Brandes' algorithm, every shortest dependency chain of the closure is weighted equally
+    let mut betweenness = vec![0.0; packages.len()];
+    for source in 0..packages.len() {
+        let mut order = Vec::new();
+        let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); packages.len()];
+        let mut paths = vec![0.0; packages.len()];
+        let mut distance: Vec<Option<usize>> = vec![None; packages.len()];
+        let mut queue = VecDeque::from([source]);
+        paths[source] = 1.0;
+        distance[source] = Some(0);
+
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            let next = distance[v].map(|d| d + 1);
+            for &w in &adjacency[v] {
+                if distance[w].is_none() {
+                    distance[w] = next;
+                    queue.push_back(w);
+                }
+                if distance[w] == next {
+                    paths[w] += paths[v];
+                    predecessors[w].push(v);
+                }
+            }
+        }
+
+        let mut dependency = vec![0.0; packages.len()];
+        while let Some(w) = order.pop() {
+            for &v in &predecessors[w] {
+                dependency[v] += paths[v] / paths[w] * (1.0 + dependency[w]);
+            }
+            if w != source {
+                betweenness[w] += dependency[w];
+            }
+        }
+    }
+
+    packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| Centrality {
+            package: p.to_string(),
+            in_degree: in_degree[i],
+            betweenness: betweenness[i],
+        })
+        .collect()
+}
+
+pub fn hubs(graph: &DepGraph, count: usize) -> Vec<Centrality> {
+    let mut hubs: Vec<Centrality> = centrality(graph)
+        .into_iter()
+        .filter(|c| c.package != graph.root)
+        .collect();
+    hubs.sort_by(|a, b| {
+        b.in_degree
+            .cmp(&a.in_degree)
+            .then(b.betweenness.total_cmp(&a.betweenness))
+            .then(a.package.cmp(&b.package))
+    });
+    hubs.truncate(count);
+    hubs
+}
+
+fn postorder(root: usize, adjacency: &[Vec<usize>]) -> Vec<usize> {
+    let mut order = Vec::new();
+    let mut visited = vec![false; adjacency.len()];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+
+    while let Some((node, child)) = stack.pop() {
+        if let Some(&next) = adjacency[node].get(child) {
+            stack.push((node, child + 1));
+            if !visited[next] {
+                visited[next] = true;
+                stack.push((next, 0));
+            }
+        } else {
+            order.push(node);
+        }
+    }
+
+    order
+}
+
+// Exclusive cost of every package: the number of packages (itself included) that can
+// only be reached from the root through it, i.e. the size of its dominator subtree.
+pub fn exclusive_costs(graph: &DepGraph) -> HashMap<String, usize> {
+    let packages = graph.packages();
+    let index: HashMap<&str, usize> = packages.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let adjacency = graph.adjacency();
+    let successors: Vec<Vec<usize>> = packages
+        .iter()
+        .map(|p| {
+            adjacency
+                .get(p)
+                .into_iter()
+                .flatten()
+                .map(|c| index[c])
+                .collect()
+        })
+        .collect();
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); packages.len()];
+    for (from, children) in successors.iter().enumerate() {
+        for &to in children {
+            predecessors[to].push(from);
+        }
+    }
+
+    let Some(&root) = index.get(graph.root.as_str()) else {
+        return HashMap::new();
+    };
+
+    // Cooper, Harvey and Kennedy's iterative dominator algorithm
+    let order = postorder(root, &successors);
+    let mut number: Vec<Option<usize>> = vec![None; packages.len()];
+    for (i, &n) in order.iter().enumerate() {
+        number[n] = Some(i);
+    }
+
+    let mut idom: Vec<Option<usize>> = vec![None; packages.len()];
+    idom[root] = Some(root);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &n in order.iter().rev().filter(|&&n| n != root) {
+            let mut new_idom = None;
+            for &p in predecessors[n].iter().filter(|&&p| idom[p].is_some()) {
+                new_idom = Some(match new_idom {
+                    None => p,
+                    Some(mut other) => {
+                        let mut p = p;
+                        while p != other {
+                            while number[p] < number[other] {
+                                p = idom[p].unwrap();
+                            }
+                            while number[other] < number[p] {
+                                other = idom[other].unwrap();
+                            }
+                        }
+                        p
+                    }
+                });
+            }
+            if new_idom.is_some() && idom[n] != new_idom {
+                idom[n] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    let mut size = vec![1; packages.len()];
+    for &n in order.iter().filter(|&&n| n != root) {
+        if let Some(d) = idom[n] {
+            size[d] += size[n];
+        }
+    }
+
+    order
+        .iter()
+        .map(|&n| (packages[n].to_string(), size[n]))
+        .collect()
+}
+
+// Number of distinct root-to-leaf dependency chains going through each edge, back edges
+// of cycles left aside.
+pub fn path_multiplicity(graph: &DepGraph) -> HashMap<(String, String), f64> {
+    let packages = graph.packages();
+    let index: HashMap<&str, usize> = packages.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+    let adjacency = graph.adjacency();
+    let successors: Vec<Vec<usize>> = packages
+        .iter()
+        .map(|p| {
+            adjacency
+                .get(p)
+                .into_iter()
+                .flatten()
+                .map(|c| index[c])
+                .collect()
+        })
+        .collect();
+
+    let Some(&root) = index.get(graph.root.as_str()) else {
+        return HashMap::new();
+    };
+
+    // depth first search keeping the forward edges only
+    let mut forward: Vec<Vec<usize>> = vec![Vec::new(); packages.len()];
+    let mut order = Vec::new();
+    let mut visited = vec![false; packages.len()];
+    let mut on_stack = vec![false; packages.len()];
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    on_stack[root] = true;
+    while let Some((node, child)) = stack.pop() {
+        if let Some(&next) = successors[node].get(child) {
+            stack.push((node, child + 1));
+            if !on_stack[next] {
+                forward[node].push(next);
+            }
+            if !visited[next] {
+                visited[next] = true;
+                on_stack[next] = true;
+                stack.push((next, 0));
+            }
+        } else {
+            on_stack[node] = false;
+            order.push(node);
+        }
+    }
+
+    let mut from = vec![0.0; packages.len()];
+    for &n in &order {
+        from[n] = if forward[n].is_empty() {
+            1.0
+        } else {
+            forward[n].iter().map(|&c| from[c]).sum()
+        };
+    }
+
+    let mut to = vec![0.0; packages.len()];
+    to[root] = 1.0;
+    for &n in order.iter().rev() {
+        for &c in &forward[n] {
+            to[c] += to[n];
+        }
+    }
+
+    let mut multiplicity = HashMap::new();
+    for &n in &order {
+        for &c in &forward[n] {
+            multiplicity.insert(
+                (packages[n].to_string(), packages[c].to_string()),
+                to[n] * from[c],
+            );
+        }
+    }
+    multiplicity
+}
+
+struct Chains<'a, 'd> {
+    to: &'a str,
+    adjacency: &'d HashMap<&'a str, Vec<&'a str>>,
+    distances: &'d HashMap<&'a str, usize>,
+    max: usize,
+    path: Vec<&'a str>,
+    paths: Vec<Vec<String>>,
+}
+
+impl<'a> Chains<'a, '_> {
+    // the chains of exactly `remaining` more dependencies from `node` to `to`
+    fn from(&mut self, node: &'a str, remaining: usize) {
+        if self.paths.len() == self.max {
+            return;
+        }
+        if node == self.to {
+            if remaining == 0 {
+                self.paths
+                    .push(self.path.iter().map(|p| p.to_string()).collect());
+            }
+            return;
+        }
+
+        for &child in self.adjacency.get(node).into_iter().flatten() {
+            // the shortest way from the child has to fit in what is left
+            let fits = self.distances.get(child).is_some_and(|&d| d < remaining);
+            if fits && !self.path.contains(&child) {
+                self.path.push(child);
+                self.from(child, remaining - 1);
+                self.path.pop();
+            }
+        }
+    }
+}
+
+// how many dependencies away from `to` are the packages having a chain to it, `to` included
+fn distances_to<'g>(
+    adjacency: &HashMap<&'g str, Vec<&'g str>>,
+    to: &'g str,
+) -> HashMap<&'g str, usize> {
+    let mut reverse: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (&from, children) in adjacency {
+        for &child in children {
+            reverse.entry(child).or_default().push(from);
+        }
+    }
+    let mut distances = HashMap::from([(to, 0)]);
+    let mut queue = VecDeque::from([to]);
+    while let Some(node) = queue.pop_front() {
+        let distance = distances[node] + 1;
+        for &parent in reverse.get(node).into_iter().flatten() {
+            if !distances.contains_key(parent) {
+                distances.insert(parent, distance);
+                queue.push_back(parent);
+            }
+        }
+    }
+    distances
+}
+
+// Up to `max` distinct dependency chains from the root to `to`, shortest first.
+pub fn dependency_paths(graph: &DepGraph, to: &str, max: usize) -> Vec<Vec<String>> {
+    let adjacency = graph.adjacency();
+    // only walk through packages that lead to `to`, so that no branch is a dead end
+    let distances = distances_to(&adjacency, to);
+
+    let Some(&shortest) = distances.get(graph.root.as_str()) else {
+        return Vec::new();
+    };
+    let mut chains = Chains {
+        to,
+        adjacency: &adjacency,
+        distances: &distances,
+        max,
+        path: vec![graph.root.as_str()],
+        paths: Vec::new(),
+    };
+    // one length after the other, a simple chain goes through every package at most once
+    for length in shortest..distances.len() {
+        chains.from(&graph.root, length);
+        if chains.paths.len() == max {
+            break;
+        }
+    }
+    chains.paths
+}
+
+// The part of the graph on the chains from the root to `on`, None when there is none.
+pub fn focus(graph: &DepGraph, on: &str) -> Option<DepGraph> {
+    let adjacency = graph.adjacency();
+    let reaching = distances_to(&adjacency, on);
+    if !reaching.contains_key(graph.root.as_str()) {
+        return None;
+    }
+
+    let mut focused = DepGraph {
+        root: graph.root.clone(),
+        nodes: graph
+            .nodes
+            .iter()
+            .filter(|n| reaching.contains_key(n.as_str()))
+            .cloned()
+            .collect(),
+        edges: graph
+            .edges
+            .iter()
+            .filter(|e| {
+                reaching.contains_key(e.from.as_str()) && reaching.contains_key(e.to.as_str())
+            })
+            .cloned()
+            .collect(),
+        info: graph
+            .info
+            .iter()
+            .filter(|(n, _)| reaching.contains_key(n.as_str()))
+            .map(|(n, i)| (n.clone(), i.clone()))
+            .collect(),
+        retired: BTreeMap::new(),
+    };
+    focused.keep_retired(graph);
+    Some(focused)
+}
+
+struct Tarjan<'a> {
+    adjacency: HashMap<&'a str, Vec<&'a str>>,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<String>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, node: &'a str) {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.low.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        let children = self.adjacency.get(node).cloned().unwrap_or_default();
+        for &child in &children {
+            if !self.index.contains_key(child) {
+                self.visit(child);
+                let low = self.low[node].min(self.low[child]);
+                self.low.insert(node, low);
+            } else if self.on_stack.contains(child) {
+                let low = self.low[node].min(self.index[child]);
+                self.low.insert(node, low);
+            }
+        }
+
+        if self.low[node] == index {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member.to_string());
+                if member == node {
+                    break;
+                }
+            }
+            if component.len() > 1 || children.contains(&node) {
+                component.sort();
+                self.components.push(component);
+            }
+        }
+    }
+}
+
+// Dependency cycles, as the strongly connected components of more than one package
+// (or of a package depending on itself), biggest first.
+pub fn cycles(graph: &DepGraph) -> Vec<Vec<String>> {
+    let mut tarjan = Tarjan {
+        adjacency: graph.adjacency(),
+        index: HashMap::new(),
+        low: HashMap::new(),
+        stack: Vec::new(),
+        on_stack: HashSet::new(),
+        components: Vec::new(),
+    };
+    for package in graph.packages() {
+        if !tarjan.index.contains_key(package) {
+            tarjan.visit(package);
+        }
+    }
+
+    let mut components = tarjan.components;
+    components.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    components
+}
+
+// Label propagation over the undirected dependency graph: every package takes the label most
+// of its neighbours have, until nothing changes. Packages are visited in a fixed order and ties
+// go to the smallest label, so that the same graph always gives the same communities. The root
+// is left out, it would tie everything together. Communities are named after their most
+// depended upon package.
+pub fn communities(graph: &DepGraph) -> HashMap<String, String> {
+    let packages: Vec<&str> = graph
+        .packages()
+        .into_iter()
+        .filter(|p| *p != graph.root)
+        .collect();
+    let index: HashMap<&str, usize> = packages.iter().enumerate().map(|(i, p)| (*p, i)).collect();
+
+    let mut neighbours: Vec<Vec<usize>> = vec![Vec::new(); packages.len()];
+    for e in &graph.edges {
+        if let (Some(&from), Some(&to)) = (index.get(e.from.as_str()), index.get(e.to.as_str())) {
+            if from != to {
+                neighbours[from].push(to);
+                neighbours[to].push(from);
+            }
+        }
+    }
+    for n in &mut neighbours {
+        n.sort();
+        n.dedup();
+    }
+
+    let mut labels: Vec<usize> = (0..packages.len()).collect();
+    // label propagation may oscillate, it settles in a handful of rounds in practice
+    for _ in 0..100 {
+        let mut changed = false;
+        for node in 0..packages.len() {
+            let mut counts: HashMap<usize, usize> = HashMap::new();
+            for &n in &neighbours[node] {
+                *counts.entry(labels[n]).or_default() += 1;
+            }
+            let Some(&most) = counts.values().max() else {
+                continue;
+            };
+            if counts.get(&labels[node]) == Some(&most) {
+                continue;
+            }
+            let best = counts
+                .iter()
+                .filter(|(_, c)| **c == most)
+                .map(|(l, _)| *l)
+                .min()
+                .unwrap_or(labels[node]);
+            labels[node] = best;
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let in_degrees = in_degrees(graph);
+    let degree = |p: &str| in_degrees.get(p).copied().unwrap_or(0);
+    let mut names: HashMap<usize, &str> = HashMap::new();
+    for (&package, &label) in packages.iter().zip(&labels) {
+        let name = names.entry(label).or_insert(package);
+        // ties go to the first name, packages are in order
+        if degree(package) > degree(name) {
+            *name = package;
+        }
+    }
+
+    packages
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.to_string(), names[&labels[i]].to_string()))
+        .collect()
+}
+
+type Slowest<'a> = HashMap<&'a str, (f64, Option<&'a str>)>;
+
+fn slowest_from<'a>(
+    node: &'a str,
+    graph: &DepGraph,
+    adjacency: &HashMap<&'a str, Vec<&'a str>>,
+    slowest: &mut Slowest<'a>,
+    stack: &mut HashSet<&'a str>,
+) -> f64 {
+    if let Some((time, _)) = slowest.get(node) {
+        return *time;
+    }
+
+    stack.insert(node);
+    let mut best = (0.0, None);
+    for &child in adjacency.get(node).into_iter().flatten() {
+        if stack.contains(child) {
+            continue;
+        }
+
+        let time = slowest_from(child, graph, adjacency, slowest, stack);
+        if time > best.0 {
+            best = (time, Some(child));
+        }
+    }
+    stack.remove(node);
+
+    let own = graph
+        .info
+        .get(node)
+        .and_then(|i| i.build_time)
+        .unwrap_or(0.0);
+    best.0 += own;
+    slowest.insert(node, best);
+    best.0
+}
+
+// The chain of builds a from-scratch build of the root has to wait for, by recorded build
+// times, with its total time. Packages without a build time take none.
+pub fn build_critical_path(graph: &DepGraph) -> (f64, Vec<String>) {
+    let adjacency = graph.adjacency();
+    let mut slowest = Slowest::new();
+    let total = slowest_from(
+        &graph.root,
+        graph,
+        &adjacency,
+        &mut slowest,
+        &mut HashSet::new(),
+    );
+
+    let mut path = vec![graph.root.clone()];
+    let mut seen = HashSet::from([graph.root.as_str()]);
+    let mut current = graph.root.as_str();
+    while let Some((_, Some(next))) = slowest.get(current) {
+        if !seen.insert(next) {
+            break;
+        }
+        path.push(next.to_string());
+        current = next;
+    }
+    (total, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::DepEdge;
+
+    // rooted at the origin of the first edge, `alone` are packages without any edge
+    fn graph_with(edges: &[(&str, &str)], alone: &[&str]) -> DepGraph {
+        let mut nodes: Vec<String> = edges
+            .iter()
+            .flat_map(|(from, to)| [from.to_string(), to.to_string()])
+            .chain(alone.iter().map(|a| a.to_string()))
+            .collect();
+        nodes.sort();
+        nodes.dedup();
+        DepGraph {
+            root: edges[0].0.to_string(),
+            info: nodes
+                .iter()
+                .map(|n| (n.clone(), Default::default()))
+                .collect(),
+            nodes,
+            edges: edges
+                .iter()
+                .map(|(from, to)| DepEdge {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    post: false,
+                    conditions: Vec::new(),
+                })
+                .collect(),
+            retired: Default::default(),
+        }
+    }
+
+    fn graph(edges: &[(&str, &str)]) -> DepGraph {
+        graph_with(edges, &[])
+    }
+
+    #[test]
+    fn paths_shortest_first() {
+        let g = graph(&[("r", "a"), ("a", "b"), ("b", "z"), ("r", "z")]);
+        assert_eq!(dependency_paths(&g, "z", 1), vec![vec!["r", "z"]]);
+        assert_eq!(
+            dependency_paths(&g, "z", 10),
+            vec![vec!["r", "z"], vec!["r", "a", "b", "z"]]
+        );
+        assert!(dependency_paths(&g, "x", 10).is_empty());
+    }
+
+    #[test]
+    fn paths_through_a_cycle() {
+        let g = graph(&[("r", "a"), ("a", "b"), ("b", "a"), ("b", "z")]);
+        assert_eq!(
+            dependency_paths(&g, "z", 10),
+            vec![vec!["r", "a", "b", "z"]]
+        );
+    }
+
+    fn costs(graph: &DepGraph) -> Vec<(String, usize)> {
+        let mut costs: Vec<(String, usize)> = exclusive_costs(graph).into_iter().collect();
+        costs.sort();
+        costs
+    }
+
+    fn pairs(pairs: &[(&str, usize)]) -> Vec<(String, usize)> {
+        pairs.iter().map(|(p, n)| (p.to_string(), *n)).collect()
+    }
+
+    #[test]
+    fn dominators_of_a_diamond() {
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "d"), ("b", "d")]);
+        assert_eq!(costs(&g), pairs(&[("a", 1), ("b", 1), ("d", 1), ("r", 4)]));
+    }
+
+    #[test]
+    fn dominators_of_a_shared_subtree() {
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "x"), ("b", "x"), ("x", "y")]);
+        assert_eq!(
+            costs(&g),
+            pairs(&[("a", 1), ("b", 1), ("r", 5), ("x", 2), ("y", 1)])
+        );
+    }
+
+    #[test]
+    fn dominators_through_a_cycle() {
+        let g = graph(&[("r", "a"), ("a", "b"), ("b", "a"), ("b", "c")]);
+        assert_eq!(costs(&g), pairs(&[("a", 3), ("b", 2), ("c", 1), ("r", 4)]));
+    }
+
+    #[test]
+    fn dominators_leave_out_unreachable_packages() {
+        let g = graph_with(&[("r", "a")], &["u"]);
+        assert_eq!(costs(&g), pairs(&[("a", 1), ("r", 2)]));
+    }
+
+    fn betweenness(graph: &DepGraph) -> Vec<(String, f64)> {
+        let mut centrality: Vec<(String, f64)> = centrality(graph)
+            .into_iter()
+            .map(|c| (c.package, c.betweenness))
+            .collect();
+        centrality.sort_by(|a, b| a.0.cmp(&b.0));
+        centrality
+    }
+
+    #[test]
+    fn betweenness_of_a_diamond() {
+        // the two shortest chains from r to d share the betweenness
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "d"), ("b", "d")]);
+        assert_eq!(
+            betweenness(&g),
+            [("a", 0.5), ("b", 0.5), ("d", 0.0), ("r", 0.0)].map(|(p, b)| (p.to_string(), b))
+        );
+        let hubs: Vec<(String, usize)> = hubs(&g, 2)
+            .into_iter()
+            .map(|h| (h.package, h.in_degree))
+            .collect();
+        assert_eq!(hubs, pairs(&[("d", 2), ("a", 1)]));
+    }
+
+    #[test]
+    fn betweenness_of_a_shared_subtree() {
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "x"), ("b", "x"), ("x", "y")]);
+        // r-x and r-y count half for a and for b, r-y, a-y and b-y all go through x
+        assert_eq!(
+            betweenness(&g),
+            [("a", 1.0), ("b", 1.0), ("r", 0.0), ("x", 3.0), ("y", 0.0)]
+                .map(|(p, b)| (p.to_string(), b))
+        );
+    }
+
+    #[test]
+    fn betweenness_through_a_cycle() {
+        let g = graph_with(&[("r", "a"), ("a", "b"), ("b", "a"), ("b", "c")], &["u"]);
+        // r-b and r-c go through a, r-c and a-c through b
+        assert_eq!(
+            betweenness(&g),
+            [("a", 2.0), ("b", 2.0), ("c", 0.0), ("r", 0.0), ("u", 0.0)]
+                .map(|(p, b)| (p.to_string(), b))
+        );
+    }
+
+    #[test]
+    fn cycles_found() {
+        let g = graph_with(
+            &[
+                ("r", "a"),
+                ("a", "b"),
+                ("b", "a"),
+                ("b", "c"),
+                ("c", "c"),
+                ("r", "x"),
+                ("x", "y"),
+                ("y", "z"),
+                ("z", "x"),
+                ("z", "a"),
+            ],
+            &["u"],
+        );
+        assert_eq!(cycles(&g), [vec!["x", "y", "z"], vec!["a", "b"], vec!["c"]]);
+    }
+
+    #[test]
+    fn no_cycles_in_a_diamond() {
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "d"), ("b", "d")]);
+        assert!(cycles(&g).is_empty());
+    }
+
+    #[test]
+    fn communities_split_at_the_root() {
+        let g = graph_with(
+            &[
+                ("r", "a1"),
+                ("r", "b1"),
+                ("a1", "a2"),
+                ("a1", "a3"),
+                ("a2", "a3"),
+                ("b1", "b2"),
+                ("b1", "b3"),
+                ("b2", "b3"),
+            ],
+            &["u"],
+        );
+        let communities = communities(&g);
+        assert!(!communities.contains_key("r"));
+        for package in ["a1", "a2", "a3"] {
+            assert_eq!(communities[package], "a3");
+        }
+        for package in ["b1", "b2", "b3"] {
+            assert_eq!(communities[package], "b3");
+        }
+        assert_eq!(communities["u"], "u");
+    }
+
+    #[test]
+    fn communities_are_stable() {
+        let g = graph(&[("r", "a"), ("r", "b"), ("a", "d"), ("b", "d"), ("d", "a")]);
+        let first = communities(&g);
+        for _ in 0..10 {
+            assert_eq!(communities(&g), first);
+        }
+    }
+}
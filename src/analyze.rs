@@ -0,0 +1,127 @@
+use std::collections::HashSet;
+use std::fs;
+
+use graphviz_rust::dot_structures::{EdgeTy, Graph, Id, NodeId, Stmt, Vertex};
+use graphviz_rust::parse;
+
+use eline::graph::{is_account, DepEdge, DepGraph, PackageInfo};
+use eline::json::parse_graph_json;
+
+pub fn is_graph_file(path: &str) -> bool {
+    path.ends_with(".json") || path.ends_with(".dot") || path.ends_with(".gv")
+}
+
+fn unquote(id: &Id) -> String {
+    let id = match id {
+        Id::Html(s) | Id::Escaped(s) | Id::Plain(s) | Id::Anonymous(s) => s,
+    };
+    id.strip_prefix('"')
+        .and_then(|i| i.strip_suffix('"'))
+        .unwrap_or(id)
+        .to_string()
+}
+
+fn vertex_name(vertex: &Vertex) -> Option<String> {
+    match vertex {
+        Vertex::N(NodeId(id, _)) => Some(unquote(id)),
+        Vertex::S(_) => None,
+    }
+}
+
+// Only what eline writes is understood: packages, accounts and their dependencies. The legend
+// and the other subgraphs are left out, and so is the metadata, dot does not keep it.
+fn parse_graph_dot(content: &str) -> Result<DepGraph, String> {
+    let (id, stmts) = match parse(content)? {
+        Graph::Graph { id, stmts, .. } | Graph::DiGraph { id, stmts, .. } => (id, stmts),
+    };
+
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Stmt::Node(node) => {
+                let name = unquote(&node.id.0);
+                if !is_account(&name) {
+                    nodes.push(name);
+                }
+            }
+            Stmt::Edge(edge) => {
+                let post = edge
+                    .attributes
+                    .iter()
+                    .any(|a| unquote(&a.0) == "style" && unquote(&a.1) == "dotted");
+                let vertices = match edge.ty {
+                    EdgeTy::Pair(from, to) => vec![from, to],
+                    EdgeTy::Chain(vertices) => vertices,
+                };
+                let names: Vec<String> = vertices.iter().filter_map(vertex_name).collect();
+                for pair in names.windows(2) {
+                    edges.push(DepEdge {
+                        from: pair[0].clone(),
+                        to: pair[1].clone(),
+                        post,
+                        conditions: Vec::new(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let info = nodes
+        .iter()
+        .map(|n| (n.clone(), PackageInfo::default()))
+        .collect();
+    let mut graph = DepGraph {
+        root: unquote(&id),
+        nodes,
+        edges,
+        info,
+        // the dot document does not tell where packages come from
+        retired: Default::default(),
+    };
+    graph.sort();
+    Ok(graph)
+}
+
+pub fn load_graph(path: &str) -> Result<DepGraph, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+    let graph = if path.ends_with(".json") {
+        parse_graph_json(&content)
+    } else {
+        parse_graph_dot(&content)
+    };
+    graph.map_err(|e| format!("{}: {}", path, e))
+}
+
+// what changed from `old` to `new`, one "+ " or "- " line per package and dependency
+pub fn graph_diff(old: &DepGraph, new: &DepGraph) -> Vec<String> {
+    let old_packages: HashSet<&str> = old.packages().into_iter().collect();
+    let new_packages: HashSet<&str> = new.packages().into_iter().collect();
+    let old_edges: HashSet<(&str, &str)> = old
+        .edges
+        .iter()
+        .map(|e| (e.from.as_str(), e.to.as_str()))
+        .collect();
+    let new_edges: HashSet<(&str, &str)> = new
+        .edges
+        .iter()
+        .map(|e| (e.from.as_str(), e.to.as_str()))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut removed: Vec<&&str> = old_packages.difference(&new_packages).collect();
+    let mut added: Vec<&&str> = new_packages.difference(&old_packages).collect();
+    removed.sort();
+    added.sort();
+    lines.extend(removed.iter().map(|p| format!("- {}", p)));
+    lines.extend(added.iter().map(|p| format!("+ {}", p)));
+
+    let mut removed: Vec<&(&str, &str)> = old_edges.difference(&new_edges).collect();
+    let mut added: Vec<&(&str, &str)> = new_edges.difference(&old_edges).collect();
+    removed.sort();
+    added.sort();
+    lines.extend(removed.iter().map(|(f, t)| format!("- {} -> {}", f, t)));
+    lines.extend(added.iter().map(|(f, t)| format!("+ {} -> {}", f, t)));
+    lines
+}
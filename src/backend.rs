@@ -0,0 +1,35 @@
+use std::rc::Rc;
+
+use crate::graph::PackageInfo;
+
+// A dependency spec tree, whatever package manager it comes from. Conditionals come already
+// evaluated against the configuration of the package they belong to.
+pub enum DepTree {
+    Package(String),
+    // a package that cannot be installed alongside, e.g. portage's "!a/b"
+    Block(String),
+    // the labels applying to the specs after them, e.g. build, run, post
+    Labels(Vec<String>),
+    // `condition` is written as the package manager does, "gtk" or "!gtk"
+    Conditional {
+        condition: String,
+        met: bool,
+        children: Vec<DepTree>,
+    },
+    All(Vec<DepTree>),
+}
+
+pub trait PackageBackend {
+    type Id;
+
+    // the ID to traverse for a package name
+    fn best_id(&self, name: &str) -> Option<Rc<Self::Id>>;
+    // makes `name` resolve to its best ID in `repository`
+    fn select(&self, name: &str, repository: &str) -> Result<(), String>;
+    fn name(&self, id: &Self::Id) -> String;
+    fn info(&self, id: &Self::Id) -> PackageInfo;
+    fn dependencies(&self, id: &Self::Id, key: &str) -> Option<DepTree>;
+    fn installed(&self, name: &str) -> bool;
+    // the repositories of removed or unavailable packages that still have `name`
+    fn retired_providers(&self, name: &str) -> Vec<String>;
+}
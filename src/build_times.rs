@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::fs;
+
+// "category/package,seconds" lines, one per recorded build. A package built several times
+// gets the mean of its builds.
+pub fn load_build_times(path: &str) -> Result<HashMap<String, f64>, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path, e))?;
+
+    let mut builds: HashMap<String, (f64, usize)> = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((package, seconds)) = line.split_once(',') else {
+            return Err(format!("{}:{}: expected 'package,seconds'", path, i + 1));
+        };
+        let seconds = match seconds.trim().parse::<f64>() {
+            // inf and NaN parse, but are no durations
+            Ok(s) if s.is_finite() && s >= 0.0 => s,
+            // a header line
+            Err(_) if i == 0 => continue,
+            _ => {
+                return Err(format!(
+                    "{}:{}: '{}' is not a duration",
+                    path,
+                    i + 1,
+                    seconds.trim()
+                ))
+            }
+        };
+        let build = builds.entry(package.trim().to_string()).or_default();
+        build.0 += seconds;
+        build.1 += 1;
+    }
+
+    Ok(builds
+        .into_iter()
+        .map(|(package, (total, count))| (package, total / count as f64))
+        .collect())
+}
+
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, seconds % 3600 / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
@@ -0,0 +1,608 @@
+use std::env;
+use std::path::Path;
+use std::process::exit;
+use std::thread;
+
+use eline::export::Registry;
+use eline::json::graph_schema;
+
+use crate::color::ColorBy;
+use crate::config::load_config;
+use crate::render::image_format;
+use crate::report::ReportFormat;
+use crate::split::SplitBy;
+
+pub const USAGE: &str = "usage: eline [COMMAND] [OPTIONS] <package>[::repository]...
+
+commands:
+  graph                         render the dependency graph of the packages (default)
+  tree                          print the dependencies as a tree, already expanded
+                                packages are marked (*)
+  stats                         only report on the dependencies: count, longest chain,
+                                hubs and dominators
+  qa                            flag the packages of the closure with missing or suspicious
+                                metadata (SUMMARY, HOMEPAGE, LICENCES, DEPENDENCIES)
+  report                        write a Markdown (or HTML) report with the graph, the
+                                dependency counts, the packages to install, the licences,
+                                dependency cycles and masked dependencies
+  revdeps                       print what depends on a dependency of the package, as a tree
+                                going up to the package: eline revdeps <package> <dependency>
+  paths                         list the dependency chains from a package to one of its
+                                dependencies: eline paths <package>[::repository] <dependency>
+  analyze                       work on a graph exported as .dot or .json, without paludis:
+                                eline analyze <file> reports on it (cycles included),
+                                eline analyze <file> <dependency> lists the chains to the
+                                dependency, eline analyze <file> <other file> diffs them
+
+options:
+  -d, --depth <N>               only follow dependencies up to N levels deep
+      --highlight-critical-path highlight the longest dependency chain in the graph
+      --focus <PACKAGE>         only keep the root, PACKAGE and the dependency chains between
+                                them, to see how PACKAGE enters the closure
+  -x, --exclude <GLOB>          leave out the packages matching GLOB (e.g. 'dev-perl/*'),
+                                adds up to ~/.config/eline/ignore and ./.elineignore
+      --deny-labels <LIST>      skip the dependencies under these labels, defaults to
+                                test,test-expensive,suggestion,built-against
+      --deny-label <LABEL>      skip the dependencies under LABEL too
+      --allow-labels <LIST>     only follow the dependencies under these labels
+      --include-accounts        keep the user/ and group/ accounts packages depend on
+      --key <NAME>              follow the dependency spec tree of the metadata key NAME
+                                instead of DEPENDENCIES
+      --show-conditionals       follow every conditional branch whatever the options, and
+                                label the edges with the options guarding them
+      --runtime-only            only follow run-time (run: and post:) dependencies
+      --build-only              only follow build-time (build: and fetch:) dependencies
+      --defer-post              do not count post-dependencies in the depth, they are not
+                                needed before the package itself
+      --prefer-installed        follow the installed version of packages already on the
+                                system instead of the best available one
+      --deps-from-repository    resolve the dependencies from the repository given with
+                                the package too, whenever it provides them
+      --backend <NAME>          read the packages through 'paludis' (default) or 'portage'
+                                (the metadata cache of /var/db/repos and /var/db/pkg)
+      --root <PATH>             graph what is installed and available in the system at PATH
+                                (a chroot, a stage, a container) instead of the host
+      --target <TRIPLE>         resolve for the multiarch target TRIPLE (e.g.
+                                armv7-unknown-linux-gnueabihf): its targets: and platform:
+                                conditionals, and the packages installed for it; with
+                                portage, the packages crossdev installed in /usr/TRIPLE
+      --add-repository <PATH>   also read the repository checked out at PATH, even if it is
+                                not configured, its packages win over the configured ones
+  -o, --output <NAME>           write NAME.dot, NAME.svg, ... instead of naming the files
+                                after the package, '-' writes the document to stdout
+      --stdout                  same as '-o -', the dot, JSON or lock document goes to stdout
+                                and every message to stderr
+      --output-dir <DIR>        write every file into DIR
+      --format <FORMAT>         render the graph as FORMAT (svg, png, pdf, ...),
+                                defaults to svg
+      --html                    also write NAME.html, a snippet embedding the graph as an
+                                inline SVG whose nodes are links
+      --node-url <TEMPLATE>     link every node to TEMPLATE, where {name}, {category} and
+                                {package} are replaced, defaults to the node anchor
+      --display                 also show the graph in the terminal, through the kitty
+                                graphics protocol or sixel (with img2sixel)
+      --no-cache                always run graphviz, instead of reusing the renders of
+                                identical graphs kept in ~/.cache/eline/renders
+      --no-image, --dot-only    do not render the graph, only write the .dot file
+      --image-only              render the graph without keeping the .dot file
+      --color-by <KEY>          fill the nodes with a color standing for their 'repository',
+                                'maintainer', 'platforms', 'community' (found by label
+                                propagation), or for their 'licence' class (permissive,
+                                copyleft, non-free)
+      --scale-nodes             grow the labels of packages the more packages depend on them
+      --weight-edges            draw edges thicker the more dependency chains go through them
+      --graph-attr <KEY=VALUE>  set a graphviz attribute on the graph (e.g. bgcolor=black)
+      --node-attr <KEY=VALUE>   set a default graphviz attribute of the nodes
+      --edge-attr <KEY=VALUE>   set a default graphviz attribute of the edges
+      --split <N>               above N packages, render an overview of the clusters linking
+                                to one image per cluster instead of a single graph
+      --split-by <KEY>          cluster by 'category' (default), 'repository', 'maintainer'
+                                (first of BUGS_TO), 'platforms' or 'community'
+      --stream                  write the dot document statement by statement instead of
+                                building it in memory first, for whole-system graphs
+  -j, --jobs <N>                render up to N graphs at the same time
+      --json                    also export the graph with its package metadata as JSON
+      --json-schema             print the JSON Schema of the --json document and exit, its
+                                format_version changes with every incompatible change
+      --lock                    also write the exact version, slot and repository of every
+                                package of the closure to <output>.lock
+      --export <LIST>           also export the graph in these formats (json, lock)
+      --build-times <CSV>       read 'category/package,seconds' build durations, show them on
+                                the nodes and report the longest chain of builds
+      --hubs <N>                report the N packages the most of the closure relies on
+      --tree                    same as the tree command
+      --seen-count              with tree, tell how many times each package is pulled in
+      --missing                 report the packages of the closure that are not installed yet
+      --dominators              report how many packages are only pulled in by each
+                                direct dependency
+      --timings                 report the time spent in each step of the run on stderr
+      --profile <NAME>          apply the [profile.NAME] section of ~/.config/eline/config
+      --report-format <FORMAT>  write the report as 'markdown' (default) or 'html'
+      --max <N>                 with paths, list at most N dependency chains (default 10)
+  -h, --help                    print this help and exit
+
+Every option can also be set in ~/.config/eline/config (depth = 3, no-image = true)
+or in the environment (ELINE_DEPTH=3, ELINE_NO_IMAGE=1). The environment takes
+precedence over the config and its profiles, command line arguments over both.";
+
+pub struct Target {
+    pub package: String,
+    pub repository: Option<String>,
+}
+
+#[derive(PartialEq)]
+pub enum Command {
+    Graph,
+    Tree,
+    Stats,
+    Paths,
+    Revdeps,
+    Qa,
+    Report,
+    Analyze,
+}
+
+impl Command {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "graph" => Some(Command::Graph),
+            "tree" => Some(Command::Tree),
+            "stats" => Some(Command::Stats),
+            "paths" => Some(Command::Paths),
+            "revdeps" => Some(Command::Revdeps),
+            "qa" => Some(Command::Qa),
+            "report" => Some(Command::Report),
+            "analyze" => Some(Command::Analyze),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend {
+    Paludis,
+    Portage,
+}
+
+impl Backend {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "paludis" => Some(Backend::Paludis),
+            "portage" => Some(Backend::Portage),
+            _ => None,
+        }
+    }
+}
+
+pub struct Options {
+    pub command: Command,
+    pub backend: Backend,
+    pub root: Option<String>,
+    pub target: Option<String>,
+    pub targets: Vec<Target>,
+    pub deps_from_repository: bool,
+    pub add_repositories: Vec<String>,
+    pub depth: usize,
+    pub highlight_critical_path: bool,
+    pub weight_edges: bool,
+    pub scale_nodes: bool,
+    pub color_by: Option<ColorBy>,
+    pub graph_attributes: Vec<(String, String)>,
+    pub node_attributes: Vec<(String, String)>,
+    pub edge_attributes: Vec<(String, String)>,
+    pub excludes: Vec<String>,
+    pub focus: Option<String>,
+    pub json: bool,
+    pub lock: bool,
+    pub exports: Vec<String>,
+    pub jobs: usize,
+    pub output: Option<String>,
+    pub stdout: bool,
+    pub output_dir: Option<String>,
+    pub format: String,
+    pub dot: bool,
+    pub image: bool,
+    pub html: bool,
+    pub display: bool,
+    pub cache: bool,
+    pub node_url: Option<String>,
+    pub split: Option<usize>,
+    pub stream: bool,
+    pub split_by: SplitBy,
+    pub prefer_installed: bool,
+    pub runtime_only: bool,
+    pub build_only: bool,
+    pub defer_post: bool,
+    pub deny_labels: Vec<String>,
+    pub allow_labels: Option<Vec<String>>,
+    pub include_accounts: bool,
+    pub show_conditionals: bool,
+    pub key: String,
+    pub build_times: Option<String>,
+    pub report_format: ReportFormat,
+    pub hubs: Option<usize>,
+    pub dominators: bool,
+    pub missing: bool,
+    pub timings: bool,
+    pub tree: bool,
+    pub seen_count: bool,
+    pub max_paths: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            command: Command::Graph,
+            backend: Backend::Paludis,
+            root: None,
+            target: None,
+            targets: Vec::new(),
+            deps_from_repository: false,
+            add_repositories: Vec::new(),
+            depth: usize::MAX,
+            highlight_critical_path: false,
+            weight_edges: false,
+            scale_nodes: false,
+            color_by: None,
+            graph_attributes: Vec::new(),
+            node_attributes: Vec::new(),
+            edge_attributes: Vec::new(),
+            excludes: Vec::new(),
+            focus: None,
+            json: false,
+            lock: false,
+            exports: Vec::new(),
+            output: None,
+            stdout: false,
+            output_dir: None,
+            format: String::from("svg"),
+            dot: true,
+            image: true,
+            html: false,
+            display: false,
+            cache: true,
+            node_url: None,
+            split: None,
+            stream: false,
+            split_by: SplitBy::Category,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+            prefer_installed: false,
+            runtime_only: false,
+            build_only: false,
+            defer_post: false,
+            deny_labels: ["test", "test-expensive", "suggestion", "built-against"]
+                .map(String::from)
+                .to_vec(),
+            allow_labels: None,
+            include_accounts: false,
+            show_conditionals: false,
+            key: String::from("DEPENDENCIES"),
+            build_times: None,
+            report_format: ReportFormat::Markdown,
+            hubs: None,
+            dominators: false,
+            missing: false,
+            timings: false,
+            tree: false,
+            seen_count: false,
+            max_paths: 10,
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' for {}", value, option))
+}
+
+fn enabled(option: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "" | "1" | "yes" | "true" | "on" => Ok(true),
+        "0" | "no" | "false" | "off" => Ok(false),
+        _ => Err(format!("invalid value '{}' for {}", value, option)),
+    }
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn key_value(option: &str, value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .ok_or(format!("--{} expects KEY=VALUE, got '{}'", option, value))
+}
+
+fn short_option(name: &str) -> Option<&'static str> {
+    match name {
+        "d" => Some("depth"),
+        "x" => Some("exclude"),
+        "o" => Some("output"),
+        "j" => Some("jobs"),
+        "h" => Some("help"),
+        _ => None,
+    }
+}
+
+impl Options {
+    fn flag(&mut self, option: &str) -> Option<(&mut bool, bool)> {
+        match option {
+            "highlight-critical-path" => Some((&mut self.highlight_critical_path, true)),
+            "json" => Some((&mut self.json, true)),
+            "lock" => Some((&mut self.lock, true)),
+            "weight-edges" => Some((&mut self.weight_edges, true)),
+            "scale-nodes" => Some((&mut self.scale_nodes, true)),
+            "stdout" => Some((&mut self.stdout, true)),
+            "no-image" | "dot-only" => Some((&mut self.image, false)),
+            "image-only" => Some((&mut self.dot, false)),
+            "html" => Some((&mut self.html, true)),
+            "display" => Some((&mut self.display, true)),
+            "no-cache" => Some((&mut self.cache, false)),
+            "deps-from-repository" => Some((&mut self.deps_from_repository, true)),
+            "prefer-installed" => Some((&mut self.prefer_installed, true)),
+            "runtime-only" => Some((&mut self.runtime_only, true)),
+            "build-only" => Some((&mut self.build_only, true)),
+            "defer-post" => Some((&mut self.defer_post, true)),
+            "include-accounts" => Some((&mut self.include_accounts, true)),
+            "show-conditionals" => Some((&mut self.show_conditionals, true)),
+            "dominators" => Some((&mut self.dominators, true)),
+            "missing" => Some((&mut self.missing, true)),
+            "stream" => Some((&mut self.stream, true)),
+            "timings" => Some((&mut self.timings, true)),
+            "tree" => Some((&mut self.tree, true)),
+            "seen-count" => Some((&mut self.seen_count, true)),
+            _ => None,
+        }
+    }
+
+    // the options adding to what they hold instead of replacing it
+    fn repeatable(option: &str) -> bool {
+        matches!(
+            option,
+            "add-repository"
+                | "export"
+                | "exclude"
+                | "deny-label"
+                | "graph-attr"
+                | "node-attr"
+                | "edge-attr"
+        )
+    }
+
+    fn takes_value(&mut self, option: &str) -> bool {
+        self.flag(option).is_none()
+    }
+
+    // Options are set the same way from every configuration layer, by their long name.
+    pub fn set(&mut self, option: &str, value: &str) -> Result<(), String> {
+        if let Some((field, on)) = self.flag(option) {
+            *field = enabled(option, value)? == on;
+            return Ok(());
+        }
+
+        match option {
+            "depth" => self.depth = number(option, value)?,
+            "key" => self.key = value.to_string(),
+            "build-times" => self.build_times = Some(value.to_string()),
+            "report-format" => {
+                self.report_format = ReportFormat::parse(value)
+                    .ok_or(format!("unknown report format '{}'", value))?
+            }
+            "backend" => {
+                self.backend =
+                    Backend::parse(value).ok_or(format!("unknown backend '{}'", value))?
+            }
+            "focus" => self.focus = Some(value.to_string()),
+            "root" => self.root = Some(value.to_string()),
+            "target" => self.target = Some(value.to_string()),
+            "add-repository" => self.add_repositories.push(value.to_string()),
+            "export" => {
+                let exporters = Registry::default();
+                for name in list(value) {
+                    if exporters.get(&name).is_none() {
+                        return Err(format!(
+                            "unknown export format '{}', expected one of {}",
+                            name,
+                            exporters.names().join(", ")
+                        ));
+                    }
+                    self.exports.push(name);
+                }
+            }
+            "exclude" => self
+                .excludes
+                .extend(value.split_whitespace().map(String::from)),
+            "output" => match value {
+                "-" => self.stdout = true,
+                output => self.output = Some(output.to_string()),
+            },
+            "node-url" => self.node_url = Some(value.to_string()),
+            "output-dir" => self.output_dir = Some(value.to_string()),
+            "format" => {
+                if image_format(value).is_none() {
+                    return Err(format!("unsupported image format '{}'", value));
+                }
+                self.format = value.to_string();
+            }
+            "color-by" => {
+                self.color_by =
+                    Some(ColorBy::parse(value).ok_or(format!("cannot color by '{}'", value))?)
+            }
+            "deny-labels" => self.deny_labels = list(value),
+            "deny-label" => self.deny_labels.extend(list(value)),
+            "allow-labels" => self.allow_labels = Some(list(value)),
+            "graph-attr" => self.graph_attributes.push(key_value(option, value)?),
+            "node-attr" => self.node_attributes.push(key_value(option, value)?),
+            "edge-attr" => self.edge_attributes.push(key_value(option, value)?),
+            "split" => self.split = Some(number(option, value)?),
+            "split-by" => {
+                self.split_by =
+                    SplitBy::parse(value).ok_or(format!("cannot split by '{}'", value))?
+            }
+            "jobs" => self.jobs = number(option, value)?,
+            "hubs" => self.hubs = Some(number(option, value)?),
+            "max" => {
+                self.max_paths = number(option, value)?;
+                if self.max_paths == 0 {
+                    return Err(String::from("--max expects at least 1 chain"));
+                }
+            }
+            // already taken into account to load the config
+            "profile" => {}
+            _ => return Err(format!("unknown option '{}'", option)),
+        }
+        Ok(())
+    }
+
+    // ELINE_DEPTH=3 is --depth 3, ELINE_NO_IMAGE=1 is --no-image and so on
+    fn apply_env(&mut self) -> Result<(), String> {
+        for (key, value) in env::vars() {
+            if let Some(option) = key.strip_prefix("ELINE_") {
+                let option = option.to_lowercase().replace('_', "-");
+                self.set(&option, &value)
+                    .map_err(|e| format!("{} (from {})", e, key))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn profile_of(args: &[String]) -> Option<String> {
+    let mut profile = env::var("ELINE_PROFILE").ok();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            profile = args.next().cloned();
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            profile = Some(value.to_string());
+        }
+    }
+    profile
+}
+
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+    let args: Vec<String> = args.collect();
+    let mut options = Options::default();
+
+    for (option, values) in load_config(profile_of(&args).as_deref())? {
+        // a list is the option given once per item, or one comma separated value
+        let values = if Options::repeatable(&option) {
+            values
+        } else {
+            vec![values.join(",")]
+        };
+        for value in values {
+            options
+                .set(&option, &value)
+                .map_err(|e| format!("{} (from the config)", e))?;
+        }
+    }
+    options.apply_env()?;
+
+    // package names always have a category, they cannot be taken for a command
+    let mut args = args.into_iter().peekable();
+    if let Some(command) = args.peek().and_then(|a| Command::parse(a)) {
+        args.next();
+        options.command = command;
+    }
+
+    while let Some(arg) = args.next() {
+        let (option, inline) = if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some((option, value)) => (option, Some(value.to_string())),
+                None => (long, None),
+            }
+        } else if let Some(short) = arg.strip_prefix('-').filter(|s| !s.is_empty()) {
+            (
+                short_option(short).ok_or(format!("unknown option '{}'", arg))?,
+                None,
+            )
+        } else {
+            options.targets.push(match arg.split_once("::") {
+                Some((package, repository)) => Target {
+                    package: package.to_string(),
+                    repository: Some(repository.to_string()),
+                },
+                None => Target {
+                    package: arg,
+                    repository: None,
+                },
+            });
+            continue;
+        };
+
+        if option == "help" {
+            println!("{}", USAGE);
+            exit(0);
+        }
+        if option == "json-schema" {
+            print!("{}", graph_schema());
+            exit(0);
+        }
+
+        let value = match inline {
+            Some(value) => value,
+            None if options.takes_value(option) => {
+                args.next().ok_or(format!("--{} expects a value", option))?
+            }
+            None => String::new(),
+        };
+        options.set(option, &value)?;
+    }
+
+    if options.targets.is_empty() {
+        return Err(String::from("no package given"));
+    }
+    if let Some(root) = options.root.as_deref().filter(|r| !Path::new(r).is_dir()) {
+        return Err(format!("--root {} is not a directory", root));
+    }
+    // shorthands for the exporters shipped with eline
+    for (enabled, name) in [(options.json, "json"), (options.lock, "lock")] {
+        if enabled && !options.exports.iter().any(|e| e == name) {
+            options.exports.push(String::from(name));
+        }
+    }
+    if options.tree && options.command == Command::Graph {
+        options.command = Command::Tree;
+    }
+    if options.command == Command::Stats {
+        options.hubs = options.hubs.or(Some(10));
+        options.dominators = true;
+    }
+    if options.command == Command::Analyze && options.targets.len() > 2 {
+        return Err(String::from(
+            "analyze expects a graph file, and a dependency or another graph file",
+        ));
+    }
+    for (command, name) in [(Command::Paths, "paths"), (Command::Revdeps, "revdeps")] {
+        if options.command == command && options.targets.len() != 2 {
+            return Err(format!(
+                "{} expects a package and one of its dependencies",
+                name
+            ));
+        }
+    }
+    if !options.dot && !options.image {
+        return Err(String::from(
+            "--dot-only and --image-only exclude each other",
+        ));
+    }
+    if options.runtime_only && options.build_only {
+        return Err(String::from(
+            "--runtime-only and --build-only exclude each other",
+        ));
+    }
+    if options.stream && options.split.is_some() {
+        return Err(String::from("--stream and --split exclude each other"));
+    }
+    if options.output.is_some() && options.targets.len() > 1 {
+        return Err(String::from("--output needs a single package"));
+    }
+    Ok(options)
+}
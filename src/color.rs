@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use eline::analysis::communities;
+
+use crate::graph::DepGraph;
+use crate::licence::classify;
+
+#[derive(Clone, Copy)]
+pub enum ColorBy {
+    Repository,
+    Licence,
+    Maintainer,
+    Platforms,
+    Community,
+}
+
+impl ColorBy {
+    pub fn parse(value: &str) -> Option<ColorBy> {
+        match value {
+            "repository" => Some(ColorBy::Repository),
+            "licence" | "license" => Some(ColorBy::Licence),
+            "maintainer" => Some(ColorBy::Maintainer),
+            "platforms" => Some(ColorBy::Platforms),
+            "community" => Some(ColorBy::Community),
+            _ => None,
+        }
+    }
+}
+
+const PALETTE: [&str; 12] = [
+    "#8dd3c7", "#ffffb3", "#bebada", "#fb8072", "#80b1d3", "#fdb462", "#b3de69", "#fccde5",
+    "#d9d9d9", "#bc80bd", "#ccebc5", "#ffed6f",
+];
+
+// FNV-1a, unlike the std hasher it is guaranteed to stay the same across releases
+pub fn fnv1a(content: &str) -> u64 {
+    content.bytes().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn stable_color(key: &str) -> &'static str {
+    PALETTE[(fnv1a(key) % PALETTE.len() as u64) as usize]
+}
+
+fn color_key(
+    graph: &DepGraph,
+    package: &str,
+    by: ColorBy,
+    communities: &HashMap<String, String>,
+) -> Option<(String, &'static str)> {
+    let info = graph.info.get(package)?;
+    match by {
+        ColorBy::Repository => {
+            let repository = info.repository.clone()?;
+            let color = stable_color(&repository);
+            Some((repository, color))
+        }
+        ColorBy::Maintainer => {
+            let maintainer = info.maintainer()?;
+            let color = stable_color(&maintainer);
+            Some((maintainer, color))
+        }
+        ColorBy::Platforms => {
+            let platforms = info.platforms_key()?;
+            let color = stable_color(&platforms);
+            Some((platforms, color))
+        }
+        ColorBy::Community => {
+            let community = communities.get(package)?.clone();
+            let color = stable_color(&community);
+            Some((community, color))
+        }
+        // packages without any LICENCES are worth a look too
+        ColorBy::Licence => {
+            let class = classify(info.licences.as_deref().unwrap_or(""));
+            Some((class.name().to_string(), class.color()))
+        }
+    }
+}
+
+// fill color of every node, and the legend as (key, color) pairs
+pub fn node_colors(
+    graph: &DepGraph,
+    by: ColorBy,
+) -> (HashMap<String, String>, Vec<(String, String)>) {
+    let mut colors = HashMap::new();
+    let communities = match by {
+        ColorBy::Community => communities(graph),
+        _ => HashMap::new(),
+    };
+    let mut legend: Vec<(String, String)> = Vec::new();
+
+    for n in &graph.nodes {
+        if let Some((key, color)) = color_key(graph, n, by, &communities) {
+            let color = color.to_string();
+            if !legend.iter().any(|(k, _)| *k == key) {
+                legend.push((key, color.clone()));
+            }
+            colors.insert(n.clone(), color);
+        }
+    }
+
+    legend.sort();
+    (colors, legend)
+}
@@ -0,0 +1,173 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|d| d.join("eline"))
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CACHE_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .map(|d| d.join("eline"))
+}
+
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+// an option and its values, a single value is a list of one
+pub type Setting = (String, Vec<String>);
+
+fn parse_value(value: &str) -> Vec<String> {
+    match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(list) => list
+            .split(',')
+            .map(|v| unquote(v.trim()))
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+            .collect(),
+        None => vec![unquote(value).to_string()],
+    }
+}
+
+// The config file holds `key = value` lines named after the long options, the
+// top-level ones apply to every run and a [profile.NAME] section only with --profile NAME.
+//
+// depth = 8
+// format = "png"
+//
+// [profile.quick]
+// depth = 2
+// exclude = ["dev-perl/*", "dev-python/*"]
+fn parse_config(content: &str, profile: Option<&str>) -> Result<(Vec<Setting>, bool), String> {
+    let mut settings = Vec::new();
+    let mut defaults = Vec::new();
+    let mut found = false;
+    let mut section: Option<String> = None;
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = Some(header.trim().to_string());
+            if let Some(name) = header.trim().strip_prefix("profile.") {
+                found |= Some(name) == profile;
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expected 'key = value'", number + 1));
+        };
+        let setting = (key.trim().replace('_', "-"), parse_value(value.trim()));
+
+        match section.as_deref().map(|s| s.strip_prefix("profile.")) {
+            None => defaults.push(setting),
+            Some(name) if name.is_some() && name == profile => settings.push(setting),
+            Some(_) => {}
+        }
+    }
+
+    defaults.append(&mut settings);
+    Ok((defaults, found))
+}
+
+// settings of the config file to apply, in order, for the given profile
+pub fn load_config(profile: Option<&str>) -> Result<Vec<Setting>, String> {
+    let path = config_dir().map(|d| d.join("config"));
+    let content = path
+        .as_ref()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .unwrap_or_default();
+
+    let (settings, found) = parse_config(&content, profile).map_err(|e| {
+        format!(
+            "{}: {}",
+            path.map(|p| p.display().to_string()).unwrap_or_default(),
+            e
+        )
+    })?;
+
+    match profile {
+        Some(profile) if !found => Err(format!("profile '{}' not found in the config", profile)),
+        _ => Ok(settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+# comments and blank lines are skipped
+depth = 8
+format = "png"
+no_image = true
+
+[profile.quick]
+depth = 2
+exclude = ["dev-perl/*", "dev-python/*", ]
+graph_attr = ["bgcolor=black", "rankdir=LR"]
+
+[other]
+depth = 5
+"#;
+
+    fn settings(pairs: &[(&str, &[&str])]) -> Vec<Setting> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.iter().map(|v| v.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn top_level_settings() {
+        let (config, found) = parse_config(CONFIG, None).unwrap();
+        assert!(!found);
+        assert_eq!(
+            config,
+            settings(&[
+                ("depth", &["8"]),
+                ("format", &["png"]),
+                ("no-image", &["true"])
+            ])
+        );
+    }
+
+    #[test]
+    fn profiles_come_after_the_defaults() {
+        let (config, found) = parse_config(CONFIG, Some("quick")).unwrap();
+        assert!(found);
+        assert_eq!(
+            config,
+            settings(&[
+                ("depth", &["8"]),
+                ("format", &["png"]),
+                ("no-image", &["true"]),
+                ("depth", &["2"]),
+                ("exclude", &["dev-perl/*", "dev-python/*"]),
+                ("graph-attr", &["bgcolor=black", "rankdir=LR"]),
+            ])
+        );
+        assert!(!parse_config(CONFIG, Some("slow")).unwrap().1);
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let error = parse_config("depth = 1\nno value here\n", None).unwrap_err();
+        assert_eq!(error, "line 2: expected 'key = value'");
+    }
+}
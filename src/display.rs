@@ -0,0 +1,149 @@
+use std::env;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+use graphviz_rust::cmd::Format;
+
+use crate::render::render;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn kitty_terminal() -> bool {
+    env::var_os("KITTY_WINDOW_ID").is_some()
+        || env::var("TERM").is_ok_and(|t| t == "xterm-kitty" || t == "xterm-ghostty")
+        || env::var("TERM_PROGRAM").is_ok_and(|t| t == "WezTerm")
+}
+
+// the kitty graphics protocol takes the png itself, base64 encoded in chunks of 4096 bytes
+fn kitty(png: &[u8]) -> io::Result<()> {
+    let encoded = base64(png);
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(4096).collect();
+
+    let mut out = io::stdout().lock();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            write!(out, "\x1b_Gf=100,a=T,m={};", more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+    writeln!(out)?;
+    out.flush()
+}
+
+// there is no sixel encoder at hand, libsixel's img2sixel does the conversion
+fn sixel(png: &[u8]) -> io::Result<()> {
+    let mut child = Command::new("img2sixel").stdin(Stdio::piped()).spawn()?;
+    child.stdin.take().unwrap().write_all(png)?;
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("img2sixel failed: {}", status)));
+    }
+    Ok(())
+}
+
+// There is no asking the terminal without putting it in raw mode, the ones known to draw sixel
+// are listed instead. xterm only does with -ti vt340, it is left out.
+fn sixel_capable(term: Option<&str>, program: Option<&str>) -> bool {
+    term.is_some_and(|t| {
+        ["foot", "foot-extra", "mlterm", "yaft-256color", "contour"].contains(&t)
+            || t.starts_with("mintty")
+    }) || program.is_some_and(|p| ["iTerm.app", "mintty", "contour"].contains(&p))
+}
+
+fn sixel_terminal() -> bool {
+    sixel_capable(
+        env::var("TERM").ok().as_deref(),
+        env::var("TERM_PROGRAM").ok().as_deref(),
+    )
+}
+
+fn check_terminal(output: &str) -> Result<(), String> {
+    if kitty_terminal() || sixel_terminal() {
+        Ok(())
+    } else {
+        Err(format!(
+            "could not display {}: the terminal speaks neither the kitty graphics protocol nor \
+             sixel",
+            output
+        ))
+    }
+}
+
+pub fn display(output: &str, dot: String, cache: bool) -> Result<(), String> {
+    // the rendering is for nothing if it cannot be shown
+    check_terminal(output)?;
+    let Some(png) = render(output, dot, Format::Png, cache) else {
+        return Err(format!("could not render {}", output));
+    };
+    show(output, &png)
+}
+
+pub fn show(output: &str, png: &[u8]) -> Result<(), String> {
+    check_terminal(output)?;
+    if kitty_terminal() {
+        kitty(png).map_err(|e| format!("could not display {}: {}", output, e))
+    } else {
+        sixel(png).map_err(|e| {
+            format!(
+                "could not display {}: sixel needs img2sixel ({})",
+                output, e
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        // the RFC 4648 test vectors
+        for (data, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(data.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xff, 0xfe, 0xfd]), "//79");
+    }
+
+    #[test]
+    fn sixel_terminals() {
+        assert!(sixel_capable(Some("foot"), None));
+        assert!(sixel_capable(Some("xterm-256color"), Some("iTerm.app")));
+        assert!(sixel_capable(Some("mintty"), None));
+        assert!(!sixel_capable(Some("xterm-256color"), None));
+        assert!(!sixel_capable(Some("linux"), None));
+        assert!(!sixel_capable(None, None));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+
+use crate::graph::DepGraph;
+use crate::json::graph_json;
+use crate::lock::graph_lock;
+
+// An output format the graph can be written to, next to the dot document and its renders.
+pub trait Exporter {
+    fn name(&self) -> &str;
+    fn extension(&self) -> &str;
+    fn write(&self, graph: &DepGraph, out: &mut dyn Write) -> io::Result<()>;
+}
+
+pub struct Json;
+
+impl Exporter for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extension(&self) -> &str {
+        "json"
+    }
+
+    fn write(&self, graph: &DepGraph, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(graph_json(graph).as_bytes())
+    }
+}
+
+pub struct Lock;
+
+impl Exporter for Lock {
+    fn name(&self) -> &str {
+        "lock"
+    }
+
+    fn extension(&self) -> &str {
+        "lock"
+    }
+
+    fn write(&self, graph: &DepGraph, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(graph_lock(graph).as_bytes())
+    }
+}
+
+pub struct Registry {
+    exporters: Vec<Box<dyn Exporter>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            exporters: Vec::new(),
+        }
+    }
+
+    // a later exporter of the same name replaces the earlier one
+    pub fn register(&mut self, exporter: Box<dyn Exporter>) {
+        self.exporters.retain(|e| e.name() != exporter.name());
+        self.exporters.push(exporter);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Exporter> {
+        self.exporters
+            .iter()
+            .find(|e| e.name() == name)
+            .map(|e| e.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.exporters.iter().map(|e| e.name()).collect()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register(Box::new(Json));
+        registry.register(Box::new(Lock));
+        registry
+    }
+}
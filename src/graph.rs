@@ -0,0 +1,174 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[derive(Clone, Default)]
+pub struct PackageInfo {
+    pub version: Option<String>,
+    pub scm: bool,
+    pub masked: bool,
+    pub slot: Option<String>,
+    pub repository: Option<String>,
+    pub summary: Option<String>,
+    pub homepage: Option<String>,
+    pub licences: Option<String>,
+    pub maintainers: Option<String>,
+    pub platforms: Option<String>,
+    pub empty_dependencies: bool,
+    // seconds, from --build-times
+    pub build_time: Option<f64>,
+    pub options: BTreeMap<String, bool>,
+    // the packages this one cannot be installed alongside
+    pub blockers: Vec<String>,
+}
+
+impl PackageInfo {
+    // BUGS_TO lists "Name <address>" or bare addresses, the first one is the main maintainer
+    pub fn maintainer(&self) -> Option<String> {
+        let first = self.maintainers.as_deref()?.split(',').next()?.trim();
+        let address = match (first.find('<'), first.find('>')) {
+            (Some(start), Some(end)) if start < end => &first[start + 1..end],
+            _ => first.split_whitespace().next()?,
+        };
+        Some(address.to_string())
+    }
+
+    // the same platforms in any order are the same key
+    pub fn platforms_key(&self) -> Option<String> {
+        let mut platforms: Vec<&str> = self.platforms.as_deref()?.split_whitespace().collect();
+        platforms.sort();
+        Some(platforms.join(","))
+    }
+}
+
+pub fn is_account(package: &str) -> bool {
+    package.starts_with("user/") || package.starts_with("group/")
+}
+
+#[derive(Clone)]
+pub struct DepEdge {
+    pub from: String,
+    pub to: String,
+    pub post: bool,
+    // options guarding the dependency, only kept with --show-conditionals
+    pub conditions: Vec<String>,
+}
+
+pub struct DepGraph {
+    pub root: String,
+    pub nodes: Vec<String>,
+    pub edges: Vec<DepEdge>,
+    pub info: HashMap<String, PackageInfo>,
+    // the unresolved dependencies only retired repositories provide, with those repositories
+    pub retired: BTreeMap<String, Vec<String>>,
+}
+
+impl DepGraph {
+    // Traversal order depends on the repositories, sorting keeps exports byte-identical from
+    // one run to the other. A dependency pulled several times is only post if it always is,
+    // and only conditional if it always is.
+    pub fn sort(&mut self) {
+        self.nodes.sort();
+        self.edges.sort_by(|a, b| {
+            (&a.from, &a.to, a.post, &a.conditions).cmp(&(&b.from, &b.to, b.post, &b.conditions))
+        });
+        // `kept` comes first and stays, `duplicate` is merged into it
+        self.edges.dedup_by(|duplicate, kept| {
+            if duplicate.from != kept.from || duplicate.to != kept.to {
+                return false;
+            }
+            kept.post &= duplicate.post;
+            if duplicate.conditions.is_empty() {
+                kept.conditions.clear();
+            }
+            true
+        });
+    }
+
+    // keeps the retired dependencies of `whole` this part of it still pulls
+    pub fn keep_retired(&mut self, whole: &DepGraph) {
+        self.retired = whole
+            .retired
+            .iter()
+            .filter(|(name, _)| self.edges.iter().any(|e| &e.to == *name))
+            .map(|(name, repositories)| (name.clone(), repositories.clone()))
+            .collect();
+    }
+
+    // includes edge targets that could not be resolved and have no node of their own
+    pub fn packages(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.nodes
+            .iter()
+            .chain(self.edges.iter().flat_map(|e| [&e.from, &e.to]))
+            .map(String::as_str)
+            .filter(|n| seen.insert(*n))
+            .collect()
+    }
+
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        let mut dependents: Vec<&str> = self
+            .edges
+            .iter()
+            .filter(|e| e.to == name)
+            .map(|e| e.from.as_str())
+            .collect();
+        dependents.dedup();
+        dependents
+    }
+
+    pub fn adjacency(&self) -> HashMap<&str, Vec<&str>> {
+        let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+        for e in &self.edges {
+            let children = adjacency.entry(e.from.as_str()).or_default();
+            if !children.contains(&e.to.as_str()) {
+                children.push(e.to.as_str());
+            }
+        }
+        adjacency
+    }
+
+    // from every package to the ones depending on it
+    pub fn reverse_adjacency(&self) -> HashMap<&str, Vec<&str>> {
+        let mut reverse: HashMap<&str, Vec<&str>> = HashMap::new();
+        for e in &self.edges {
+            let parents = reverse.entry(e.to.as_str()).or_default();
+            if !parents.contains(&e.from.as_str()) {
+                parents.push(e.from.as_str());
+            }
+        }
+        reverse
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(post: bool, conditions: &[&str]) -> DepEdge {
+        DepEdge {
+            from: String::from("a/b"),
+            to: String::from("c/d"),
+            post,
+            conditions: conditions.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn duplicate_edges_are_merged() {
+        let mut graph = DepGraph {
+            root: String::from("a/b"),
+            nodes: vec![String::from("a/b"), String::from("c/d")],
+            edges: vec![edge(true, &[]), edge(false, &["gtk"])],
+            info: HashMap::new(),
+            retired: BTreeMap::new(),
+        };
+        graph.sort();
+        assert_eq!(graph.edges.len(), 1);
+        assert!(!graph.edges[0].post);
+        assert!(graph.edges[0].conditions.is_empty());
+
+        graph.edges = vec![edge(true, &["gtk"]), edge(true, &["gtk"])];
+        graph.sort();
+        assert!(graph.edges[0].post);
+        assert_eq!(graph.edges[0].conditions, ["gtk"]);
+    }
+}
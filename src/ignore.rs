@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::config_dir;
+
+fn read_patterns(path: PathBuf, patterns: &mut Vec<String>) {
+    if let Ok(content) = fs::read_to_string(path) {
+        patterns.extend(
+            content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(String::from),
+        );
+    }
+}
+
+pub fn ignore_patterns() -> Vec<String> {
+    let mut patterns = Vec::new();
+    if let Some(dir) = config_dir() {
+        read_patterns(dir.join("ignore"), &mut patterns);
+    }
+    read_patterns(PathBuf::from(".elineignore"), &mut patterns);
+    patterns
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+// a pattern without a '/' is matched against the category, so "dev-perl" drops it whole
+pub fn is_ignored(patterns: &[String], name: &str) -> bool {
+    let category = name.split('/').next().unwrap_or(name);
+    patterns.iter().any(|p| {
+        let subject = if p.contains('/') { name } else { category };
+        glob_match(p.as_bytes(), subject.as_bytes())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        let matches = |pattern: &str, name: &str| glob_match(pattern.as_bytes(), name.as_bytes());
+        assert!(matches("dev-perl/*", "dev-perl/Foo-Bar"));
+        assert!(matches("*", ""));
+        assert!(matches("*/*-bin", "app-misc/foo-bin"));
+        assert!(matches("sys-libs/zlib", "sys-libs/zlib"));
+        assert!(matches("dev-lang/python?", "dev-lang/python3"));
+        assert!(matches("**a", "a"));
+        assert!(!matches("dev-lang/python?", "dev-lang/python"));
+        assert!(!matches("dev-perl/*", "dev-python/foo"));
+        assert!(!matches("*-bin", "app-misc/foo-binary"));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn categories_or_packages() {
+        let patterns = [String::from("dev-perl"), String::from("*/*-doc")];
+        assert!(is_ignored(&patterns, "dev-perl/Foo"));
+        assert!(is_ignored(&patterns, "app-doc/foo-doc"));
+        assert!(!is_ignored(&patterns, "dev-perlx/Foo"));
+        assert!(!is_ignored(&patterns, "app-doc/foo"));
+        assert!(!is_ignored(&[], "app-doc/foo"));
+    }
+}
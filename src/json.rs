@@ -0,0 +1,689 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::graph::{DepEdge, DepGraph, PackageInfo};
+
+pub fn escape(content: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in content.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn optional(value: &Option<String>) -> String {
+    value.as_deref().map(escape).unwrap_or(String::from("null"))
+}
+
+// bumped on every change of the document consumers could break on
+pub const FORMAT_VERSION: u32 = 2;
+
+#[derive(Clone, Copy)]
+enum Kind {
+    String,
+    OptionalString,
+    Boolean,
+    OptionalNumber,
+    // an object of booleans
+    Flags,
+    Strings,
+}
+
+impl Kind {
+    fn schema(self) -> &'static str {
+        match self {
+            Kind::String => r#"{"type": "string"}"#,
+            Kind::OptionalString => r#"{"type": ["string", "null"]}"#,
+            Kind::Boolean => r#"{"type": "boolean"}"#,
+            Kind::OptionalNumber => r#"{"type": ["number", "null"]}"#,
+            Kind::Flags => r#"{"type": "object", "additionalProperties": {"type": "boolean"}}"#,
+            Kind::Strings => r#"{"type": "array", "items": {"type": "string"}}"#,
+        }
+    }
+}
+
+// a field of an object, its JSON type and its value
+type Field = (&'static str, Kind, String);
+
+// Destructuring keeps the fields in step with PackageInfo, a field added there does not build
+// until it is written here or explicitly left out. The schema is made of the same fields.
+fn node_fields(name: &str, info: &PackageInfo) -> Vec<Field> {
+    let PackageInfo {
+        version,
+        scm,
+        masked,
+        slot,
+        repository,
+        summary,
+        homepage,
+        licences,
+        maintainers,
+        platforms,
+        empty_dependencies,
+        build_time,
+        options,
+        blockers,
+    } = info;
+    let options: Vec<String> = options
+        .iter()
+        .map(|(o, enabled)| format!("{}: {}", escape(o), enabled))
+        .collect();
+    let blockers: Vec<String> = blockers.iter().map(|b| escape(b)).collect();
+    vec![
+        ("name", Kind::String, escape(name)),
+        ("version", Kind::OptionalString, optional(version)),
+        ("scm", Kind::Boolean, scm.to_string()),
+        ("masked", Kind::Boolean, masked.to_string()),
+        ("slot", Kind::OptionalString, optional(slot)),
+        ("repository", Kind::OptionalString, optional(repository)),
+        ("summary", Kind::OptionalString, optional(summary)),
+        ("homepage", Kind::OptionalString, optional(homepage)),
+        ("licences", Kind::OptionalString, optional(licences)),
+        ("maintainers", Kind::OptionalString, optional(maintainers)),
+        ("platforms", Kind::OptionalString, optional(platforms)),
+        (
+            "empty_dependencies",
+            Kind::Boolean,
+            empty_dependencies.to_string(),
+        ),
+        (
+            "build_time",
+            Kind::OptionalNumber,
+            // JSON has no infinity nor NaN
+            build_time
+                .filter(|t| t.is_finite())
+                .map_or(String::from("null"), |t| t.to_string()),
+        ),
+        (
+            "options",
+            Kind::Flags,
+            format!("{{{}}}", options.join(", ")),
+        ),
+        (
+            "blockers",
+            Kind::Strings,
+            format!("[{}]", blockers.join(", ")),
+        ),
+    ]
+}
+
+fn edge_fields(edge: &DepEdge) -> Vec<Field> {
+    let DepEdge {
+        from,
+        to,
+        post,
+        conditions,
+    } = edge;
+    let conditions: Vec<String> = conditions.iter().map(|c| escape(c)).collect();
+    vec![
+        ("from", Kind::String, escape(from)),
+        ("to", Kind::String, escape(to)),
+        ("post", Kind::Boolean, post.to_string()),
+        (
+            "conditions",
+            Kind::Strings,
+            format!("[{}]", conditions.join(", ")),
+        ),
+    ]
+}
+
+fn object(fields: &[Field]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, _, value)| format!("{}: {}", escape(key), value))
+        .collect();
+    format!("{{{}}}", fields.join(", "))
+}
+
+fn object_schema(fields: &[Field], indent: &str) -> String {
+    let properties: Vec<String> = fields
+        .iter()
+        .map(|(key, kind, _)| format!("{}    {}: {}", indent, escape(key), kind.schema()))
+        .collect();
+    let required: Vec<String> = fields.iter().map(|(key, _, _)| escape(key)).collect();
+    format!(
+        "{{\n{i}  \"type\": \"object\",\n{i}  \"required\": [{}],\n{i}  \"properties\": {{\n{}\n{i}  }}\n{i}}}",
+        required.join(", "),
+        properties.join(",\n"),
+        i = indent
+    )
+}
+
+// the JSON Schema (draft 2020-12) of what graph_json writes
+pub fn graph_schema() -> String {
+    format!(
+        r#"{{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "eline dependency graph, format version {version}",
+  "type": "object",
+  "required": ["format_version", "root", "nodes", "edges", "retired"],
+  "properties": {{
+    "format_version": {{"const": {version}}},
+    "root": {{"type": "string"}},
+    "nodes": {{
+      "type": "array",
+      "items": {nodes}
+    }},
+    "edges": {{
+      "type": "array",
+      "items": {edges}
+    }},
+    "retired": {{
+      "type": "object",
+      "additionalProperties": {strings}
+    }}
+  }}
+}}
+"#,
+        version = FORMAT_VERSION,
+        strings = Kind::Strings.schema(),
+        nodes = object_schema(&node_fields("", &PackageInfo::default()), "      "),
+        edges = object_schema(
+            &edge_fields(&DepEdge {
+                from: String::new(),
+                to: String::new(),
+                post: false,
+                conditions: Vec::new(),
+            }),
+            "      "
+        ),
+    )
+}
+
+pub fn graph_json(graph: &DepGraph) -> String {
+    let mut out = String::from("{\n");
+    out += &format!("  \"format_version\": {},\n", FORMAT_VERSION);
+    out += &format!("  \"root\": {},\n", escape(&graph.root));
+
+    let unknown = PackageInfo::default();
+    out += "  \"nodes\": [";
+    for (i, name) in graph.nodes.iter().enumerate() {
+        let info = graph.info.get(name).unwrap_or(&unknown);
+        out += if i == 0 { "\n    " } else { ",\n    " };
+        out += &object(&node_fields(name, info));
+    }
+    out += "\n  ],\n";
+
+    out += "  \"edges\": [";
+    for (i, e) in graph.edges.iter().enumerate() {
+        out += if i == 0 { "\n    " } else { ",\n    " };
+        out += &object(&edge_fields(e));
+    }
+    out += "\n  ],\n";
+
+    // the dependencies only retired repositories provide, and those repositories
+    let retired: Vec<String> = graph
+        .retired
+        .iter()
+        .map(|(name, repositories)| {
+            let repositories: Vec<String> = repositories.iter().map(|r| escape(r)).collect();
+            format!("{}: [{}]", escape(name), repositories.join(", "))
+        })
+        .collect();
+    out += &format!("  \"retired\": {{{}}}\n}}\n", retired.join(", "));
+
+    out
+}
+
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> &[Value] {
+        match self {
+            Value::Array(values) => values,
+            _ => &[],
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((i, c)) => Err(format!("expected '{}' at {}, found '{}'", expected, i, c)),
+            None => Err(format!("expected '{}', found the end", expected)),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            match self.chars.next() {
+                Some((_, c)) if c == expected => {}
+                _ => return Err(format!("expected '{}'", word)),
+            }
+        }
+        Ok(value)
+    }
+
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let code: String = (0..4)
+            .filter_map(|_| self.chars.next())
+            .map(|(_, c)| c)
+            .collect();
+        if code.len() != 4 || !code.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("invalid escape \\u{}", code));
+        }
+        u32::from_str_radix(&code, 16).map_err(|_| format!("invalid escape \\u{}", code))
+    }
+
+    fn digits(&mut self, number: &mut String) -> usize {
+        let mut count = 0;
+        while let Some((_, c)) = self.chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            number.push(c);
+            count += 1;
+        }
+        count
+    }
+
+    // -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Result<Value, String> {
+        let mut number = String::new();
+        if let Some((_, c)) = self.chars.next_if(|(_, c)| *c == '-') {
+            number.push(c);
+        }
+        let integer = self.digits(&mut number);
+        let leading_zero = number.trim_start_matches('-').starts_with('0') && integer > 1;
+        let mut valid = integer > 0 && !leading_zero;
+        if let Some((_, c)) = self.chars.next_if(|(_, c)| *c == '.') {
+            number.push(c);
+            valid &= self.digits(&mut number) > 0;
+        }
+        if let Some((_, c)) = self.chars.next_if(|(_, c)| *c == 'e' || *c == 'E') {
+            number.push(c);
+            if let Some((_, c)) = self.chars.next_if(|(_, c)| *c == '+' || *c == '-') {
+                number.push(c);
+            }
+            valid &= self.digits(&mut number) > 0;
+        }
+        match number.parse() {
+            Ok(n) if valid => Ok(Value::Number(n)),
+            _ => Err(format!("invalid value '{}'", number)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'b')) => s.push('\u{8}'),
+                    Some((_, 'f')) => s.push('\u{c}'),
+                    Some((_, 'u')) => {
+                        let code = self.code_unit()?;
+                        let code = if (0xd800..0xdc00).contains(&code) {
+                            // a character outside the BMP, written as a surrogate pair
+                            if self.chars.next().map(|(_, c)| c) != Some('\\')
+                                || self.chars.next().map(|(_, c)| c) != Some('u')
+                            {
+                                return Err(String::from("unpaired surrogate"));
+                            }
+                            let low = self.code_unit()?;
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err(String::from("unpaired surrogate"));
+                            }
+                            0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)
+                        } else {
+                            code
+                        };
+                        s.push(char::from_u32(code).ok_or("unpaired surrogate")?);
+                    }
+                    Some((_, c @ ('"' | '\\' | '/'))) => s.push(c),
+                    Some((_, c)) => return Err(format!("invalid escape \\{}", c)),
+                    None => return Err(String::from("unterminated string")),
+                },
+                Some((_, c)) => s.push(c),
+                None => return Err(String::from("unterminated string")),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.chars.peek().map(|(_, c)| *c) {
+            Some('{') => {
+                self.chars.next();
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == '}').is_some() {
+                    return Ok(Value::Object(fields));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, '}')) => return Ok(Value::Object(fields)),
+                        _ => return Err(String::from("expected ',' or '}' in an object")),
+                    }
+                }
+            }
+            Some('[') => {
+                self.chars.next();
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.chars.next_if(|(_, c)| *c == ']').is_some() {
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, ']')) => return Ok(Value::Array(values)),
+                        _ => return Err(String::from("expected ',' or ']' in an array")),
+                    }
+                }
+            }
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Null),
+            Some(_) => self.number(),
+            None => Err(String::from("unexpected end of the document")),
+        }
+    }
+}
+
+pub fn parse(content: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: content.char_indices().peekable(),
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some((i, _)) => Err(format!("trailing content at {}", i)),
+    }
+}
+
+// reads back what graph_json writes
+pub fn parse_graph_json(content: &str) -> Result<DepGraph, String> {
+    let document = parse(content)?;
+    // documents from before the field are version 0, they read the same
+    let version = document
+        .get("format_version")
+        .and_then(Value::as_f64)
+        .unwrap_or(0.0);
+    if version > FORMAT_VERSION as f64 {
+        return Err(format!(
+            "format version {} is newer than the supported {}",
+            version, FORMAT_VERSION
+        ));
+    }
+    let root = document
+        .get("root")
+        .and_then(Value::as_str)
+        .ok_or("no root package")?
+        .to_string();
+
+    let text = |node: &Value, key: &str| node.get(key).and_then(Value::as_str).map(String::from);
+    let flag = |node: &Value, key: &str| node.get(key).and_then(Value::as_bool).unwrap_or(false);
+    let strings = |node: &Value, key: &str| -> Vec<String> {
+        node.get(key)
+            .map_or(&[][..], Value::as_array)
+            .iter()
+            .filter_map(|s| s.as_str().map(String::from))
+            .collect()
+    };
+
+    let mut nodes = Vec::new();
+    let mut info = HashMap::new();
+    for node in document.get("nodes").map_or(&[][..], Value::as_array) {
+        let name = text(node, "name").ok_or("a node has no name")?;
+        let options = match node.get("options") {
+            Some(Value::Object(options)) => options
+                .iter()
+                .filter_map(|(o, enabled)| Some((o.clone(), enabled.as_bool()?)))
+                .collect(),
+            _ => BTreeMap::new(),
+        };
+        info.insert(
+            name.clone(),
+            PackageInfo {
+                version: text(node, "version"),
+                scm: flag(node, "scm"),
+                masked: flag(node, "masked"),
+                slot: text(node, "slot"),
+                repository: text(node, "repository"),
+                summary: text(node, "summary"),
+                homepage: text(node, "homepage"),
+                licences: text(node, "licences"),
+                maintainers: text(node, "maintainers"),
+                platforms: text(node, "platforms"),
+                empty_dependencies: flag(node, "empty_dependencies"),
+                build_time: node.get("build_time").and_then(Value::as_f64),
+                options,
+                blockers: strings(node, "blockers"),
+            },
+        );
+        nodes.push(name);
+    }
+
+    let mut edges = Vec::new();
+    for edge in document.get("edges").map_or(&[][..], Value::as_array) {
+        edges.push(DepEdge {
+            from: text(edge, "from").ok_or("an edge has no origin")?,
+            to: text(edge, "to").ok_or("an edge has no target")?,
+            post: flag(edge, "post"),
+            conditions: strings(edge, "conditions"),
+        });
+    }
+
+    let retired = match document.get("retired") {
+        Some(Value::Object(retired)) => retired
+            .iter()
+            .map(|(name, repositories)| {
+                let repositories = repositories
+                    .as_array()
+                    .iter()
+                    .filter_map(|r| r.as_str().map(String::from))
+                    .collect();
+                (name.clone(), repositories)
+            })
+            .collect(),
+        _ => BTreeMap::new(),
+    };
+
+    let mut graph = DepGraph {
+        root,
+        nodes,
+        edges,
+        info,
+        retired,
+    };
+    graph.sort();
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DepGraph {
+        let mut options = BTreeMap::new();
+        options.insert(String::from("ssl"), true);
+        options.insert(String::from("doc"), false);
+        let root = PackageInfo {
+            version: Some(String::from("1.0-r1")),
+            scm: true,
+            slot: Some(String::from("0")),
+            repository: Some(String::from("arbor")),
+            summary: Some(String::from(
+                "a \"quoted\" summary\twith\\escapes\n\u{1} and é ✓ 😀",
+            )),
+            build_time: Some(12.5),
+            options,
+            ..Default::default()
+        };
+        let zlib = PackageInfo {
+            masked: true,
+            empty_dependencies: true,
+            blockers: vec![String::from("sys-libs/zlib-ng")],
+            ..Default::default()
+        };
+        DepGraph {
+            root: String::from("app-misc/root"),
+            nodes: vec![String::from("app-misc/root"), String::from("sys-libs/zlib")],
+            edges: vec![
+                DepEdge {
+                    from: String::from("app-misc/root"),
+                    to: String::from("dev-libs/old"),
+                    post: false,
+                    conditions: Vec::new(),
+                },
+                DepEdge {
+                    from: String::from("app-misc/root"),
+                    to: String::from("sys-libs/zlib"),
+                    post: true,
+                    conditions: vec![String::from("!static")],
+                },
+            ],
+            info: HashMap::from([
+                (String::from("app-misc/root"), root),
+                (String::from("sys-libs/zlib"), zlib),
+            ]),
+            retired: BTreeMap::from([(
+                String::from("dev-libs/old"),
+                vec![String::from("graveyard")],
+            )]),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let json = graph_json(&sample());
+        let graph = parse_graph_json(&json).unwrap();
+        assert_eq!(graph_json(&graph), json);
+        assert_eq!(graph.retired, sample().retired);
+        assert_eq!(
+            graph.info["app-misc/root"].summary.as_deref(),
+            sample().info["app-misc/root"].summary.as_deref()
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let value = parse(r#""\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00""#).unwrap();
+        assert_eq!(value.as_str(), Some("\"\\/\u{8}\u{c}\n\r\té😀"));
+    }
+
+    #[test]
+    fn numbers() {
+        for (text, number) in [("0", 0.0), ("-12", -12.0), ("1.5e3", 1500.0), ("2E-1", 0.2)] {
+            assert_eq!(parse(text).unwrap().as_f64(), Some(number), "{}", text);
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        for text in [
+            "",
+            "01",
+            "1.",
+            ".5",
+            "-",
+            "+1",
+            "1e",
+            "1.2.3",
+            "1-2",
+            "tru",
+            "[1,]",
+            "[1 2]",
+            r#"{"a" 1}"#,
+            r#"{"a": 1,}"#,
+            r#""abc"#,
+            r#""\q""#,
+            r#""\u12""#,
+            r#""\ud800""#,
+            r#""\ud800\u0041""#,
+            r#""\ude00""#,
+            "1 2",
+        ] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn published_schema() {
+        assert_eq!(include_str!("../schema/graph.schema.json"), graph_schema());
+    }
+
+    #[test]
+    fn non_finite_build_times() {
+        let mut graph = sample();
+        for time in [f64::INFINITY, f64::NAN] {
+            graph.info.get_mut("app-misc/root").unwrap().build_time = Some(time);
+            let graph = parse_graph_json(&graph_json(&graph)).unwrap();
+            assert_eq!(graph.info["app-misc/root"].build_time, None);
+        }
+    }
+
+    #[test]
+    fn newer_format_versions_are_refused() {
+        let json = format!(
+            r#"{{"format_version": {}, "root": "a/b", "nodes": [], "edges": []}}"#,
+            FORMAT_VERSION + 1
+        );
+        assert!(parse_graph_json(&json).is_err());
+        assert!(parse_graph_json(r#"{"root": "a/b", "nodes": [], "edges": []}"#).is_ok());
+    }
+}
@@ -0,0 +1,13 @@
+pub mod analysis;
+pub mod backend;
+pub mod export;
+pub mod graph;
+pub mod json;
+pub mod lock;
+mod packages;
+pub mod paludis;
+pub mod portage;
+pub mod qa;
+pub mod timings;
+pub mod traversal;
+pub mod tree;
@@ -0,0 +1,155 @@
+// From the most to the least comfortable to ship.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LicenceClass {
+    Permissive,
+    Copyleft,
+    Unknown,
+    NonFree,
+}
+
+impl LicenceClass {
+    pub fn name(self) -> &'static str {
+        match self {
+            LicenceClass::Permissive => "permissive",
+            LicenceClass::Copyleft => "copyleft",
+            LicenceClass::Unknown => "unknown",
+            LicenceClass::NonFree => "non-free",
+        }
+    }
+
+    pub fn color(self) -> &'static str {
+        match self {
+            LicenceClass::Permissive => "#b3de69",
+            LicenceClass::Copyleft => "#80b1d3",
+            LicenceClass::Unknown => "#d9d9d9",
+            LicenceClass::NonFree => "#fb8072",
+        }
+    }
+}
+
+const PERMISSIVE: [&str; 24] = [
+    "MIT",
+    "BSD-",
+    "ISC",
+    "Apache-",
+    "ZLIB",
+    "Boost-",
+    "PSF-",
+    "public-domain",
+    "Unlicense",
+    "CC0",
+    "X11",
+    "Artistic",
+    "WTFPL",
+    "PHP-",
+    "openssl",
+    "OFL-",
+    "Ruby",
+    "UoI-NCSA",
+    "libpng",
+    "IJG",
+    "FTL",
+    "HPND",
+    "NTP",
+    "BZIP2",
+];
+
+const COPYLEFT: [&str; 11] = [
+    "GPL-", "LGPL-", "AGPL-", "MPL-", "EPL-", "CDDL", "CC-BY-SA", "FDL-", "GFDL", "OSL-", "EUPL",
+];
+
+const NON_FREE: [&str; 8] = [
+    "no-source-code",
+    "all-rights-reserved",
+    "CC-BY-NC",
+    "CC-BY-ND",
+    "unRAR",
+    "Oracle",
+    "NVIDIA",
+    "EULA",
+];
+
+fn classify_one(licence: &str) -> LicenceClass {
+    let known = |prefixes: &[&str]| prefixes.iter().any(|p| licence.starts_with(p));
+    // CC-BY-NC-SA is a share alike licence, but not a free one
+    if known(&NON_FREE) {
+        LicenceClass::NonFree
+    } else if known(&COPYLEFT) {
+        LicenceClass::Copyleft
+    } else if known(&PERMISSIVE) {
+        LicenceClass::Permissive
+    } else {
+        LicenceClass::Unknown
+    }
+}
+
+// The class of the group starting at `pos`, None for an empty one. `any` is for the inside of
+// || ( ... ), where the least restrictive alternative is enough.
+fn classify_group(tokens: &[&str], pos: &mut usize, any: bool) -> Option<LicenceClass> {
+    let mut classes = Vec::new();
+    while let Some(&token) = tokens.get(*pos) {
+        *pos += 1;
+        let class = match token {
+            ")" => break,
+            "(" => classify_group(tokens, pos, false),
+            // conditionals are all taken into account, whatever the options
+            _ if token == "||" || token.ends_with('?') => {
+                if tokens.get(*pos) != Some(&"(") {
+                    continue;
+                }
+                *pos += 1;
+                classify_group(tokens, pos, token == "||")
+            }
+            _ => Some(classify_one(token)),
+        };
+        classes.extend(class);
+    }
+    if any {
+        classes.into_iter().min()
+    } else {
+        classes.into_iter().max()
+    }
+}
+
+// LICENCES is a dependency-like spec: "GPL-2 || ( MIT BSD-3 ) gtk? ( LGPL-2.1 )". The most
+// restrictive licence wins, but of alternatives the least restrictive one is picked.
+pub fn classify(licences: &str) -> LicenceClass {
+    let tokens: Vec<&str> = licences.split_whitespace().collect();
+    classify_group(&tokens, &mut 0, false).unwrap_or(LicenceClass::Unknown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_licences() {
+        assert_eq!(classify("MIT"), LicenceClass::Permissive);
+        assert_eq!(classify("BSD-3"), LicenceClass::Permissive);
+        assert_eq!(classify("LGPL-2.1"), LicenceClass::Copyleft);
+        assert_eq!(classify("CC-BY-SA-4.0"), LicenceClass::Copyleft);
+        assert_eq!(classify("CC-BY-NC-SA-4.0"), LicenceClass::NonFree);
+        assert_eq!(classify("NVIDIA-r2"), LicenceClass::NonFree);
+        assert_eq!(classify("Something-Else"), LicenceClass::Unknown);
+        assert_eq!(classify(""), LicenceClass::Unknown);
+    }
+
+    #[test]
+    fn the_most_restrictive_wins() {
+        assert_eq!(classify("GPL-2 || ( MIT BSD-3 )"), LicenceClass::Copyleft);
+        assert_eq!(classify("MIT gtk? ( LGPL-2.1 )"), LicenceClass::Copyleft);
+        assert_eq!(classify("MIT ( unRAR )"), LicenceClass::NonFree);
+        assert_eq!(classify("MIT Something-Else"), LicenceClass::Unknown);
+        assert_eq!(classify("|| ( MIT ) doc? ( )"), LicenceClass::Permissive);
+        // dual licensing leaves the choice
+        assert_eq!(classify("|| ( GPL-2 MIT )"), LicenceClass::Permissive);
+        assert_eq!(
+            classify("|| ( unRAR ( GPL-2 MIT ) )"),
+            LicenceClass::Copyleft
+        );
+        assert_eq!(
+            classify("BSD-3 || ( GPL-2 AGPL-3 )"),
+            LicenceClass::Copyleft
+        );
+    }
+}
@@ -0,0 +1,25 @@
+use crate::graph::DepGraph;
+
+// One exact paludis spec per resolved package, =category/package-version:slot::repository,
+// so the lock can be fed back to the package manager.
+pub fn graph_lock(graph: &DepGraph) -> String {
+    let mut out = format!("# resolved closure of {}\n", graph.root);
+    for name in &graph.nodes {
+        let Some(info) = graph.info.get(name) else {
+            continue;
+        };
+        let Some(version) = &info.version else {
+            continue;
+        };
+
+        out += &format!("={}-{}", name, version);
+        if let Some(slot) = &info.slot {
+            out += &format!(":{}", slot);
+        }
+        if let Some(repository) = &info.repository {
+            out += &format!("::{}", repository);
+        }
+        out.push('\n');
+    }
+    out
+}
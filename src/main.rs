@@ -1,258 +1,995 @@
-use std::collections::{HashMap, HashSet};
+mod analyze;
+mod build_times;
+mod cli;
+mod color;
+mod config;
+mod display;
+mod ignore;
+mod licence;
+mod render;
+mod report;
+mod split;
+mod stream;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 
 use graphviz_rust::cmd::Format;
-use graphviz_rust::dot_structures::{Edge, EdgeTy, Graph, Id, Node, NodeId, Stmt, Vertex};
+use graphviz_rust::dot_structures::{
+    Attribute, Edge, EdgeTy, Graph, GraphAttributes, Id, Node, NodeId, Stmt, Subgraph, Vertex,
+};
+use graphviz_rust::print;
 use graphviz_rust::printer::PrinterContext;
-use graphviz_rust::{exec_dot, print};
-use paludis_rs::{DependenciesLabel, DependencySpecTree, Environment, PackageID};
+use paludis_rs::Environment;
 
-fn authorized_labels(labels: &Vec<DependenciesLabel>) -> bool {
-    let labels = labels
-        .iter()
-        .map(|l| l.text().to_string())
-        .collect::<Vec<_>>();
-    return !(labels.contains(&String::from("test"))
-        || labels.contains(&String::from("suggestion"))
-        || labels.contains(&String::from("test-expensive"))
-        || labels.contains(&String::from("built-against")));
+use eline::analysis::{
+    build_critical_path, critical_path, cycles, dependency_paths, exclusive_costs, focus, hubs,
+    in_degrees, path_multiplicity,
+};
+use eline::backend::PackageBackend;
+use eline::export::Registry;
+use eline::graph::{self, is_account, DepGraph, PackageInfo};
+use eline::paludis::PaludisBackend;
+use eline::portage::PortageBackend;
+use eline::qa::qa_issues;
+use eline::timings::{report, timed, Phase};
+use eline::traversal::{traverse, Event, LabelFilter, Traversal};
+use eline::tree::{render_reverse_tree, render_tree};
+
+use crate::analyze::{graph_diff, is_graph_file, load_graph};
+use crate::build_times::{format_duration, load_build_times};
+use crate::cli::{parse_args, Backend, Command, Options, Target, USAGE};
+use crate::color::node_colors;
+use crate::display::display;
+use crate::ignore::{ignore_patterns, is_ignored};
+use crate::render::{render, render_all, render_streamed, temporary};
+use crate::report::write_report;
+use crate::split::split_graph;
+use crate::stream::{write_dot, write_dot_file};
+
+fn graphiz_escape(content: &str) -> String {
+    "\"".to_owned() + content + "\""
 }
 
-fn clean_deps(deps: Vec<DependencySpecTree>) -> Vec<DependencySpecTree> {
-    let mut res = Vec::new();
-    let mut skip = false;
+fn node_maker(node: String) -> Stmt {
+    let node = graphiz_escape(&node);
 
-    deps.into_iter().for_each(|d: DependencySpecTree| {
-        if skip {
-            if let DependencySpecTree::Labels(labels) = d {
-                if authorized_labels(&labels) {
-                    skip = false;
-                    res.push(DependencySpecTree::Labels(labels));
-                }
+    Stmt::Node(Node {
+        id: NodeId(Id::Plain(node), None),
+        attributes: Vec::new(),
+    })
+}
+
+fn edge_maker(from: String, to: String) -> Stmt {
+    let from = graphiz_escape(&from);
+    let to = graphiz_escape(&to);
+
+    Stmt::Edge(Edge {
+        ty: EdgeTy::Pair(
+            Vertex::N(NodeId(Id::Plain(from), None)),
+            Vertex::N(NodeId(Id::Plain(to), None)),
+        ),
+        attributes: Vec::new(),
+    })
+}
+
+fn attribute(key: &str, value: &str) -> Attribute {
+    Attribute(Id::Plain(key.to_string()), Id::Plain(graphiz_escape(value)))
+}
+
+fn highlight(attributes: &mut Vec<Attribute>) {
+    attributes.push(attribute("color", "red"));
+    attributes.push(attribute("penwidth", "2"));
+}
+
+#[derive(Default)]
+struct DotStyle {
+    highlighted: Vec<String>,
+    edge_weights: HashMap<(String, String), f64>,
+    node_colors: HashMap<String, String>,
+    font_sizes: HashMap<String, f64>,
+    graph_attributes: Vec<(String, String)>,
+    node_attributes: Vec<(String, String)>,
+    edge_attributes: Vec<(String, String)>,
+    node_url: Option<String>,
+    legend: Vec<(String, String)>,
+}
+
+// Statements are handed over one at a time, so that they can be written out as they come.
+fn dot_statements(graph: &DepGraph, style: &DotStyle, emit: &mut dyn FnMut(Stmt)) {
+    // defaults only apply to what comes after them
+    let attributes = |pairs: &[(String, String)]| -> Vec<Attribute> {
+        pairs.iter().map(|(k, v)| attribute(k, v)).collect()
+    };
+    if !style.graph_attributes.is_empty() {
+        emit(Stmt::GAttribute(GraphAttributes::Graph(attributes(
+            &style.graph_attributes,
+        ))));
+    }
+    if !style.node_attributes.is_empty() {
+        emit(Stmt::GAttribute(GraphAttributes::Node(attributes(
+            &style.node_attributes,
+        ))));
+    }
+    if !style.edge_attributes.is_empty() {
+        emit(Stmt::GAttribute(GraphAttributes::Edge(attributes(
+            &style.edge_attributes,
+        ))));
+    }
+
+    let highlighted = &style.highlighted;
+    let heaviest = style.edge_weights.values().copied().fold(1.0, f64::max);
+
+    for n in &graph.nodes {
+        let mut node = node_maker(n.clone());
+        if let Stmt::Node(ref mut node) = node {
+            // anchors to link to from the page embedding the graph
+            node.attributes.push(attribute("id", n));
+            if let Some(template) = &style.node_url {
+                let url = template
+                    .replace("{name}", n)
+                    .replace("{category}", n.split('/').next().unwrap_or(n))
+                    .replace("{package}", n.split('/').nth(1).unwrap_or(n));
+                node.attributes.push(attribute("URL", &url));
             }
-        } else {
-            if let DependencySpecTree::Labels(labels) = d {
-                if !authorized_labels(&labels) {
-                    skip = true;
-                } else {
-                    res.push(DependencySpecTree::Labels(labels));
+            if let Some(size) = style.font_sizes.get(n) {
+                node.attributes
+                    .push(attribute("fontsize", &format!("{:.1}", size)));
+            }
+            let mut styles = Vec::new();
+            let mut badges = Vec::new();
+            let fill = style.node_colors.get(n);
+            if let Some(color) = fill {
+                styles.push("filled");
+                node.attributes.push(attribute("fillcolor", color));
+            }
+            if graph.info.get(n).is_some_and(|i| i.scm) {
+                styles.push("dashed");
+                badges.push("[scm]");
+            }
+            if graph.info.get(n).is_some_and(|i| i.masked) {
+                badges.push("[masked]");
+                node.attributes.push(attribute("color", "orange"));
+                if fill.is_none() {
+                    styles.push("filled");
+                    node.attributes.push(attribute("fillcolor", "#ffe8cc"));
                 }
-            } else {
-                res.push(d);
+            }
+            let build_time = graph
+                .info
+                .get(n)
+                .and_then(|i| i.build_time)
+                .map(format_duration);
+            if let Some(time) = &build_time {
+                badges.push(time);
+            }
+            if !styles.is_empty() {
+                node.attributes.push(attribute("style", &styles.join(",")));
+            }
+            if !badges.is_empty() {
+                node.attributes
+                    .push(attribute("label", &format!("{}\\n{}", n, badges.join(" "))));
+            }
+            if highlighted.contains(n) {
+                highlight(&mut node.attributes);
+            }
+            if let Some(info) = graph.info.get(n).filter(|i| !i.options.is_empty()) {
+                let options: Vec<String> = info
+                    .options
+                    .iter()
+                    .map(|(o, enabled)| format!("{}{}", if *enabled { "+" } else { "-" }, o))
+                    .collect();
+                node.attributes.push(attribute(
+                    "tooltip",
+                    &format!("options: {}", options.join(" ")),
+                ));
             }
         }
-    });
+        emit(node);
+    }
 
-    res
+    // accounts are not packages of their own, they only show up as edge targets
+    let mut accounts: Vec<&str> = graph
+        .packages()
+        .into_iter()
+        .filter(|p| is_account(p) && !graph.info.contains_key(*p))
+        .collect();
+    accounts.sort();
+    for account in accounts {
+        emit(Stmt::Node(Node {
+            id: NodeId(Id::Plain(graphiz_escape(account)), None),
+            attributes: vec![
+                attribute("shape", "house"),
+                attribute("color", "slateblue"),
+                attribute("fontcolor", "slateblue"),
+            ],
+        }));
+    }
+
+    // no repository still in use provides them, their dependents are about to break
+    for (name, repositories) in &graph.retired {
+        emit(Stmt::Node(Node {
+            id: NodeId(Id::Plain(graphiz_escape(name)), None),
+            attributes: vec![
+                attribute(
+                    "label",
+                    &format!("{}\\n[{}]", name, repositories.join(", ")),
+                ),
+                attribute("color", "red"),
+                attribute("style", "filled"),
+                attribute("fillcolor", "#ffd6d6"),
+            ],
+        }));
+    }
+
+    for e in &graph.edges {
+        let mut edge = edge_maker(e.from.clone(), e.to.clone());
+        if let Stmt::Edge(ref mut edge) = edge {
+            if e.post {
+                edge.attributes.push(attribute("style", "dotted"));
+            }
+            if !e.conditions.is_empty() {
+                edge.attributes.push(attribute(
+                    "label",
+                    &format!("[{}]", e.conditions.join(", ")),
+                ));
+            }
+            if let Some(weight) = style.edge_weights.get(&(e.from.clone(), e.to.clone())) {
+                // path counts grow exponentially, a log scale keeps the widths readable
+                let width = 1.0 + 7.0 * weight.ln() / heaviest.ln().max(1.0);
+                edge.attributes
+                    .push(attribute("penwidth", &format!("{:.2}", width)));
+            }
+            if highlighted
+                .windows(2)
+                .any(|w| w[0] == e.from && w[1] == e.to)
+            {
+                highlight(&mut edge.attributes);
+            }
+        }
+        emit(edge);
+    }
+
+    if !style.legend.is_empty() {
+        let mut legend = vec![Stmt::Attribute(attribute("label", "legend"))];
+        for (key, color) in &style.legend {
+            legend.push(Stmt::Node(Node {
+                id: NodeId(Id::Plain(graphiz_escape(&format!("legend: {}", key))), None),
+                attributes: vec![
+                    attribute("label", key),
+                    attribute("shape", "box"),
+                    attribute("style", "filled"),
+                    attribute("fillcolor", color),
+                ],
+            }));
+        }
+        emit(Stmt::Subgraph(Subgraph {
+            id: Id::Plain(String::from("cluster_legend")),
+            stmts: legend,
+        }));
+    }
 }
 
-fn _dep_fold<N, E>(
-    pkg_name: &str,
-    pkg_dep: DependencySpecTree,
-    packages: &HashMap<String, PackageID>,
-    node_fn: fn(String) -> N,
-    edge_fn: fn(String, String) -> E,
-    nodes: &mut Vec<N>,
-    edges: &mut Vec<E>,
-    depth: usize,
-    depth_max: usize,
-    mark: &mut HashSet<String>,
-) {
-    match pkg_dep {
-        paludis_rs::DependencySpecTree::None => {}
-        paludis_rs::DependencySpecTree::NamedSet(_) => {}
-        paludis_rs::DependencySpecTree::Labels(_) => {}
-        paludis_rs::DependencySpecTree::Package(p) => {
-            let name = p.full_name();
-            if !name.starts_with("user/") && !name.starts_with("group/") {
-                edges.push(edge_fn(pkg_name.to_string(), name));
-
-                let pid = packages.get(&p.full_name());
-                if let Some(pid) = pid {
-                    dep_fold(
-                        pid, packages, node_fn, edge_fn, nodes, edges, depth, depth_max, mark,
-                    );
+fn dot_graph(graph: &DepGraph, style: &DotStyle) -> Graph {
+    let mut stmts = Vec::new();
+    dot_statements(graph, style, &mut |stmt| stmts.push(stmt));
+    Graph::Graph {
+        id: Id::Plain(graphiz_escape(&graph.root)),
+        strict: false,
+        stmts,
+    }
+}
+
+fn package_graph<B: PackageBackend>(
+    package: &str,
+    backend: &B,
+    options: &Options,
+    excludes: &[String],
+    build_times: &HashMap<String, f64>,
+) -> Option<DepGraph> {
+    let labels = LabelFilter {
+        deny: options.deny_labels.clone(),
+        allow: if options.runtime_only {
+            Some(["run", "post"].map(String::from).to_vec())
+        } else if options.build_only {
+            Some(["build", "fetch"].map(String::from).to_vec())
+        } else {
+            options.allow_labels.clone()
+        },
+    };
+
+    let pid = backend.best_id(package)?;
+    let exclude = |name: &str| is_ignored(excludes, name);
+    let traversal = Traversal {
+        backend,
+        depth_max: options.depth,
+        defer_post: options.defer_post,
+        include_accounts: options.include_accounts,
+        show_conditionals: options.show_conditionals,
+        key: &options.key,
+        exclude: &exclude,
+        labels: &labels,
+    };
+
+    let mut info: HashMap<String, PackageInfo> = HashMap::new();
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    let mut choices: HashMap<String, BTreeMap<String, bool>> = HashMap::new();
+    let mut blockers: HashMap<String, BTreeSet<String>> = HashMap::new();
+    // nothing breaks off the walk, the whole graph is wanted
+    _ = timed(Phase::Folding, || {
+        traverse(&traversal, &pid, |event| {
+            match event {
+                Event::Node { name, id, .. } => {
+                    let mut package_info = timed(Phase::Metadata, || backend.info(id));
+                    package_info.build_time = build_times.get(name).copied();
+                    info.insert(name.to_string(), package_info);
+                    nodes.push(name.to_string());
+                }
+                Event::Edge(edge) => edges.push(edge.clone()),
+                Event::Choice {
+                    package,
+                    option,
+                    enabled,
+                } => {
+                    choices
+                        .entry(package.to_string())
+                        .or_default()
+                        .insert(option.to_string(), enabled);
+                }
+                Event::Block { package, blocked } => {
+                    blockers
+                        .entry(package.to_string())
+                        .or_default()
+                        .insert(blocked.to_string());
                 }
             }
+            ControlFlow::Continue(())
+        })
+    });
+    for (name, options) in choices {
+        if let Some(package_info) = info.get_mut(&name) {
+            package_info.options = options;
         }
-        DependencySpecTree::Conditional(_, _) => {}
-        paludis_rs::DependencySpecTree::All(all) => {
-            let all = clean_deps(all);
-            for a in all {
-                _dep_fold(
-                    pkg_name, a, packages, node_fn, edge_fn, nodes, edges, depth, depth_max, mark,
-                );
-            }
+    }
+    for (name, blocked) in blockers {
+        if let Some(package_info) = info.get_mut(&name) {
+            package_info.blockers = blocked.into_iter().collect();
         }
     }
+
+    let mut graph = DepGraph {
+        root: package.to_string(),
+        nodes,
+        edges,
+        info,
+        retired: BTreeMap::new(),
+    };
+    graph.sort();
+    graph.retired = retired_dependencies(&graph, backend);
+    Some(graph)
 }
 
-fn dep_fold<N, E>(
-    package: &PackageID,
-    packages: &HashMap<String, PackageID>,
-    node_fn: fn(String) -> N,
-    edge_fn: fn(String, String) -> E,
-    nodes: &mut Vec<N>,
-    edges: &mut Vec<E>,
-    depth: usize,
-    depth_max: usize,
-    mark: &mut HashSet<String>,
-) {
-    let name = package.name();
-    if mark.contains(&name) {
+// with --stdout, the standard output is kept for the exported document alone
+macro_rules! say {
+    ($options:expr, $($arg:tt)*) => {
+        if $options.stdout {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
+
+fn not_installed<'g, B: PackageBackend>(graph: &'g DepGraph, backend: &B) -> Vec<&'g String> {
+    graph
+        .nodes
+        .iter()
+        .filter(|n| !backend.installed(n))
+        .collect()
+}
+
+// the dependencies nothing but retired repositories provide, with those repositories
+fn retired_dependencies<B: PackageBackend>(
+    graph: &DepGraph,
+    backend: &B,
+) -> BTreeMap<String, Vec<String>> {
+    let mut unresolved: Vec<&String> = graph
+        .edges
+        .iter()
+        .map(|e| &e.to)
+        .filter(|to| !graph.info.contains_key(*to))
+        .collect();
+    unresolved.sort();
+    unresolved.dedup();
+
+    unresolved
+        .into_iter()
+        .map(|name| (name.clone(), backend.retired_providers(name)))
+        .filter(|(_, repositories)| !repositories.is_empty())
+        .collect()
+}
+
+fn print_retired(graph: &DepGraph, options: &Options) {
+    let retired = &graph.retired;
+    if retired.is_empty() {
         return;
-    } else {
-        mark.insert(name.clone());
     }
+    say!(
+        options,
+        "warning: {} dependencies are only provided by retired repositories, their dependents \
+         are about to break:",
+        retired.len()
+    );
+    for (name, repositories) in retired {
+        say!(
+            options,
+            "  {} ({}), needed by {}",
+            name,
+            repositories.join(", "),
+            graph.dependents(name).join(", ")
+        );
+    }
+}
 
-    nodes.push(node_fn(package.name()));
+// returns the critical path, to highlight it
+// `missing` are the packages left to install, when there is an environment to tell
+fn print_stats(graph: &DepGraph, options: &Options, missing: Option<Vec<&String>>) -> Vec<String> {
+    say!(
+        options,
+        "\n{}: {} dependencies found",
+        graph.root,
+        graph.nodes.len().saturating_sub(1)
+    );
 
-    if depth == depth_max {
-        return;
+    let masked: Vec<&String> = graph
+        .nodes
+        .iter()
+        .filter(|n| graph.info.get(*n).is_some_and(|i| i.masked))
+        .collect();
+    if !masked.is_empty() {
+        say!(
+            options,
+            "warning: {} packages only resolve to masked versions:",
+            masked.len()
+        );
+        for package in masked {
+            say!(options, "  {}", package);
+        }
     }
 
-    if let Some(key) = package.metadata_key("DEPENDENCIES") {
-        match key.value() {
-            paludis_rs::MetadataValue::DependencySpecTree(d) => _dep_fold(
-                &name,
-                d,
-                packages,
-                node_fn,
-                edge_fn,
-                nodes,
-                edges,
-                depth + 1,
-                depth_max,
-                mark,
-            ),
-            _ => {}
+    if let Some(missing) = missing {
+        say!(
+            options,
+            "\n{} of {} packages are not installed:",
+            missing.len(),
+            graph.nodes.len()
+        );
+        for package in missing {
+            say!(options, "  {}", package);
         }
     }
-}
 
-fn graphiz_escape(content: &str) -> String {
-    "\"".to_owned() + content + "\""
-}
+    let mut path = critical_path(graph);
+    say!(
+        options,
+        "longest dependency chain ({} packages): {}",
+        path.len(),
+        path.join(" -> ")
+    );
+    // with build times, the chain worth highlighting is the one a build waits for
+    if options.build_times.is_some() {
+        let total;
+        (total, path) = build_critical_path(graph);
+        say!(
+            options,
+            "longest build chain ({}): {}",
+            format_duration(total),
+            path.join(" -> ")
+        );
+    }
 
-fn node_maker(node: String) -> Stmt {
-    println!("package : {}", node);
-    let node = graphiz_escape(&node);
+    if let Some(count) = options.hubs {
+        say!(
+            options,
+            "\n{:>9} {:>12}  package",
+            "in-degree",
+            "betweenness"
+        );
+        for hub in hubs(graph, count) {
+            say!(
+                options,
+                "{:>9} {:>12.1}  {}",
+                hub.in_degree,
+                hub.betweenness,
+                hub.package
+            );
+        }
+    }
 
-    Stmt::Node(Node {
-        id: NodeId(Id::Plain(node), None),
-        attributes: Vec::new(),
-    })
+    if options.dominators {
+        let costs = exclusive_costs(graph);
+        let mut direct: Vec<(&str, usize)> = graph
+            .adjacency()
+            .get(graph.root.as_str())
+            .into_iter()
+            .flatten()
+            .map(|d| (*d, costs.get(*d).copied().unwrap_or(0)))
+            .collect();
+        direct.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+        say!(options, "\n{:>9}  direct dependency", "exclusive");
+        for (dependency, cost) in &direct {
+            say!(options, "{:>9}  {}", cost, dependency);
+        }
+        let exclusive: usize = direct.iter().map(|(_, cost)| cost).sum();
+        say!(
+            options,
+            "{:>9}  shared by several direct dependencies",
+            costs.len().saturating_sub(exclusive + 1)
+        );
+    }
+
+    path
 }
 
-fn edge_maker(from: String, to: String) -> Stmt {
-    let from = graphiz_escape(&from);
-    let to = graphiz_escape(&to);
+fn graph_style(graph: &DepGraph, path: Vec<String>, options: &Options) -> DotStyle {
+    let mut style = DotStyle {
+        graph_attributes: options.graph_attributes.clone(),
+        node_attributes: options.node_attributes.clone(),
+        edge_attributes: options.edge_attributes.clone(),
+        node_url: options
+            .node_url
+            .clone()
+            .or(options.html.then(|| String::from("#{name}"))),
+        ..DotStyle::default()
+    };
+    if options.highlight_critical_path {
+        style.highlighted = path;
+    }
+    if options.weight_edges {
+        style.edge_weights = path_multiplicity(graph);
+    }
+    if options.scale_nodes {
+        let in_degrees = in_degrees(graph);
+        let most = in_degrees.values().copied().max().unwrap_or(0) as f64;
+        for (package, count) in in_degrees {
+            // 14 is the graphviz default, the most depended upon package gets three times that
+            let size = 14.0 + 28.0 * (1.0 + count as f64).ln() / (1.0 + most).ln().max(1.0);
+            style.font_sizes.insert(package.to_string(), size);
+        }
+    }
+    if let Some(by) = options.color_by {
+        (style.node_colors, style.legend) = node_colors(graph, by);
+    }
+    style
+}
 
-    Stmt::Edge(Edge {
-        ty: EdgeTy::Pair(
-            Vertex::N(NodeId(Id::Plain(from), None)),
-            Vertex::N(NodeId(Id::Plain(to), None)),
-        ),
-        attributes: Vec::new(),
-    })
+// every file written for a graph is named after this, whatever its extension
+fn output_base(graph: &DepGraph, options: &Options) -> String {
+    let output = options
+        .output
+        .clone()
+        .unwrap_or(graph.root.replace("/", "-"));
+    match &options.output_dir {
+        Some(dir) => {
+            _ = std::fs::create_dir_all(dir);
+            Path::new(dir).join(output).to_string_lossy().to_string()
+        }
+        None => output,
+    }
 }
 
-fn best_id_for(mut ids: Vec<PackageID>) -> Option<PackageID> {
-    ids.sort_by_key(|id| id.version());
+fn export_graph(
+    graph: &DepGraph,
+    path: Vec<String>,
+    options: &Options,
+    exporters: &Registry,
+) -> Vec<(String, String)> {
+    let style = graph_style(graph, path, options);
+
+    if options.stdout {
+        if let Some(exporter) = options.exports.first().and_then(|e| exporters.get(e)) {
+            if let Err(e) = exporter.write(graph, &mut std::io::stdout().lock()) {
+                eprintln!("error: could not write the graph: {}", e);
+            }
+        } else if options.stream {
+            let written = timed(Phase::Printing, || {
+                write_dot(graph, &style, std::io::stdout().lock())
+            });
+            if let Err(e) = written {
+                eprintln!("error: could not write the graph: {}", e);
+            }
+        } else {
+            let dot = dot_graph(graph, &style);
+            println!(
+                "{}",
+                timed(Phase::Printing, || print(
+                    dot,
+                    &mut PrinterContext::default()
+                ))
+            );
+        }
+        return Vec::new();
+    }
+
+    let output = output_base(graph, options);
+    for exporter in options.exports.iter().filter_map(|e| exporters.get(e)) {
+        let path = format!("{}.{}", output, exporter.extension());
+        let written = std::fs::File::create(&path)
+            .and_then(|file| exporter.write(graph, &mut std::io::BufWriter::new(file)));
+        if let Err(e) = written {
+            eprintln!("error: could not write {}: {}", path, e);
+        }
+    }
 
-    if ids.len() == 0 {
-        return None;
+    if options.stream {
+        // without the .dot file to keep, the document goes through a temporary one
+        let path = if options.dot {
+            PathBuf::from(output.clone() + ".dot")
+        } else {
+            temporary(&output, "dot")
+        };
+        let written = timed(Phase::Printing, || write_dot_file(graph, &style, &path));
+        if let Err(e) = written {
+            eprintln!("error: could not write {}: {}", path.display(), e);
+            return Vec::new();
+        }
+        // graphviz reads the file itself, the document is never read back
+        let rendered = timed(Phase::Rendering, || {
+            render_streamed(&path, &output, options)
+        });
+        if let Err(e) = rendered {
+            eprintln!("error: {}", e);
+        }
+        if !options.dot {
+            _ = std::fs::remove_file(&path);
+        }
+        return Vec::new();
     }
 
-    let allscm = ids
-        .iter()
-        .fold(true, |acc, id| id.version().is_scm() && acc);
+    let mut renders = Vec::new();
+    if options.split.is_some_and(|size| graph.nodes.len() > size) {
+        let (overview, parts) =
+            split_graph(graph, options.split_by, &output, &options.format, &style);
+        // NAME.dot holds the overview, as the image next to it does
+        let s = timed(Phase::Printing, || {
+            print(overview, &mut PrinterContext::default())
+        });
+        if options.dot {
+            _ = std::fs::write(output.clone() + ".dot", s.as_str());
+        }
+        renders.push((output, s));
+        for (part_output, part) in parts {
+            let s = timed(Phase::Printing, || {
+                print(part, &mut PrinterContext::default())
+            });
+            if options.dot {
+                _ = std::fs::write(part_output.clone() + ".dot", s.as_str());
+            }
+            renders.push((part_output, s));
+        }
+    } else {
+        let dot = dot_graph(graph, &style);
+        let s = timed(Phase::Printing, || {
+            print(dot, &mut PrinterContext::default())
+        });
+        if options.dot {
+            _ = std::fs::write(output.clone() + ".dot", s.as_str());
+        }
+        renders.push((output, s));
+    }
 
-    if allscm {
-        ids.pop()
+    if options.image || options.html || options.display {
+        renders
     } else {
-        ids.retain(|id| !id.version().is_scm());
-        ids.pop()
+        Vec::new()
     }
 }
 
-fn package_graph(
-    package: &str,
-    packages: &mut HashMap<String, PackageID>,
-    depth: usize,
-) -> (Option<Graph>, usize) {
-    let mut nodes: Vec<Stmt> = Vec::new();
-    let mut edges: Vec<Stmt> = Vec::new();
-    let mut mark: HashSet<String> = HashSet::new();
-
-    let pid = packages.get(package);
-    if let Some(pid) = pid {
-        println!("{:?}", pid.metadata_key("DEPENDENCIES").map(|v| v.value()));
-        println!("\n\n");
-        println!(
-            "{:?}",
-            pid.metadata_key("DEPENDENCIES").map(|v| v.value_str())
-        );
+fn print_paths(graph: &DepGraph, to: &str, options: &Options) -> Result<(), String> {
+    let paths = dependency_paths(graph, to, options.max_paths);
+    if paths.is_empty() {
+        return Err(format!("{} does not depend on {}", graph.root, to));
+    }
+    for path in paths {
+        println!("{}", path.join(" -> "));
+    }
+    Ok(())
+}
 
-        dep_fold(
-            &pid, packages, node_maker, edge_maker, &mut nodes, &mut edges, 0, depth, &mut mark,
-        );
-    } else {
-        return (None, 0);
+fn focused(graph: DepGraph, options: &Options) -> Result<DepGraph, String> {
+    match &options.focus {
+        Some(on) => focus(&graph, on).ok_or(format!("{} does not depend on {}", graph.root, on)),
+        None => Ok(graph),
     }
+}
 
-    let dep_number = (nodes.len() - 1).max(0);
-    nodes.append(&mut edges);
-    let graph = Graph::Graph {
-        id: Id::Plain(graphiz_escape(package)),
-        strict: false,
-        stmts: nodes,
+// Every command shares the environment and the traversal, they only differ in what they do
+// with the graph. Returns the graphs left to render.
+fn process_target<B: PackageBackend>(
+    target: &Target,
+    options: &Options,
+    backend: &B,
+    excludes: &[String],
+    exporters: &Registry,
+    build_times: &HashMap<String, f64>,
+) -> Result<Vec<(String, String)>, String> {
+    let package = target.package.as_str();
+    let Some(graph) = package_graph(package, backend, options, excludes, build_times) else {
+        return Ok(Vec::new());
     };
+    let graph = focused(graph, options)?;
 
-    (Some(graph), dep_number)
-}
+    let missing = || options.missing.then(|| not_installed(&graph, backend));
+    if options.command != Command::Qa {
+        print_retired(&graph, options);
+    }
+    match options.command {
+        Command::Graph => {
+            let path = print_stats(&graph, options, missing());
+            Ok(export_graph(&graph, path, options, exporters))
+        }
+        Command::Tree => {
+            print!("\n{}", render_tree(&graph, options.seen_count));
+            print_stats(&graph, options, missing());
+            Ok(Vec::new())
+        }
+        Command::Stats => {
+            print_stats(&graph, options, missing());
+            Ok(Vec::new())
+        }
+        Command::Report => {
+            let style = graph_style(&graph, critical_path(&graph), options);
+            let dot = dot_graph(&graph, &style);
+            let dot = timed(Phase::Printing, || {
+                print(dot, &mut PrinterContext::default())
+            });
 
-fn main() {
-    let package = "dev-texlive/texlive-xetex";
-    let mut packages = HashMap::new();
-    let e = Environment::default();
-
-    for r in e.repositories_names() {
-        if r != "installed"
-            && r != "accounts"
-            && r != "graveyard"
-            && r != "unavailable"
-            && r != "unavailable-unofficial"
-            && r != "unwritten"
-            && r != "graveyard"
-            && r != "repository"
-            && r != "installed-accounts"
-            && r != "installed_unpackaged"
-        {
-            let repo = e.fetch_repository(&r).unwrap();
-            for p in repo.package_names() {
-                if !packages.contains_key(&p) {
-                    if let Some(pck) = best_id_for(repo.package_ids(&p)) {
-                        packages.insert(p, pck);
-                    }
+            let output = output_base(&graph, options);
+            let image = output.clone() + ".svg";
+            let svg = timed(Phase::Rendering, || {
+                render(&image, dot, Format::Svg, options.cache)
+            });
+            if let Some(svg) = &svg {
+                _ = std::fs::write(&image, svg);
+            }
+            // the report sits next to the image, it links to it by its file name
+            let link = Path::new(&image)
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or(image.clone());
+
+            let report = write_report(
+                &graph,
+                &not_installed(&graph, backend),
+                &|name| backend.installed(name),
+                svg.as_deref().map(|svg| (link.as_str(), svg)),
+                options.report_format,
+            );
+            let path = format!("{}.{}", output, options.report_format.extension());
+            std::fs::write(&path, report)
+                .map_err(|e| format!("could not write {}: {}", path, e))?;
+            say!(options, "report written to {}", path);
+            Ok(Vec::new())
+        }
+        Command::Qa => {
+            let mut issues = qa_issues(&graph);
+            for (name, repositories) in &graph.retired {
+                for dependent in graph.dependents(name) {
+                    issues.push((
+                        dependent.to_string(),
+                        format!("{} is only in {}", name, repositories.join(", ")),
+                    ));
                 }
             }
+            issues.sort_by(|a, b| a.0.cmp(&b.0));
+            say!(
+                options,
+                "\n{}: {} issues in {} packages",
+                graph.root,
+                issues.len(),
+                graph.nodes.len()
+            );
+            for (package, issue) in issues {
+                say!(options, "  {}: {}", package, issue);
+            }
+            Ok(Vec::new())
         }
+        Command::Paths => {
+            print_paths(&graph, &options.targets[1].package, options).map(|_| Vec::new())
+        }
+        Command::Revdeps => {
+            let dependency = &options.targets[1].package;
+            if dependency == &graph.root || !graph.edges.iter().any(|e| &e.to == dependency) {
+                return Err(format!("{} does not depend on {}", graph.root, dependency));
+            }
+            print!("{}", render_reverse_tree(&graph, dependency));
+            Ok(Vec::new())
+        }
+        // handled before the environment is even loaded
+        Command::Analyze => unreachable!(),
     }
+}
 
-    let (graph, dep_num) = package_graph(package, &mut packages, 132);
-    println!("\n{} dependencies found", dep_num);
+// works on exported graphs alone, without any paludis environment
+fn analyze(options: &Options) -> Result<(), String> {
+    let graph = focused(load_graph(&options.targets[0].package)?, options)?;
+    match options.targets.get(1).map(|t| t.package.as_str()) {
+        Some(other) if is_graph_file(other) => {
+            let other = focused(load_graph(other)?, options)?;
+            for line in graph_diff(&graph, &other) {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        Some(dependency) => print_paths(&graph, dependency, options),
+        None => {
+            print_stats(&graph, options, None);
+            let cycles = cycles(&graph);
+            say!(options, "{} dependency cycles", cycles.len());
+            for cycle in cycles {
+                say!(options, "  {}", cycle.join(", "));
+            }
+            Ok(())
+        }
+    }
+}
+
+// Everything past the environment setup, the same for every package manager.
+fn run<B: PackageBackend>(backend: &B, options: &Options) -> bool {
+    let mut excludes = ignore_patterns();
+    excludes.extend(options.excludes.iter().cloned());
+
+    let exporters = Registry::default();
+    let build_times = match &options.build_times {
+        Some(path) => load_build_times(path).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            exit(1);
+        }),
+        None => HashMap::new(),
+    };
+
+    let mut failed = false;
+    let mut renders = Vec::new();
+    let targets = match options.command {
+        Command::Paths | Command::Revdeps => &options.targets[..1],
+        _ => &options.targets[..],
+    };
+    for target in targets {
+        let package = target.package.as_str();
+        if let Some(repository) = &target.repository {
+            if let Err(err) = backend.select(package, repository) {
+                eprintln!("error: {}", err);
+                failed = true;
+                continue;
+            }
+        }
 
-    if let Some(graph) = graph {
-        let s = print(graph, &mut PrinterContext::default());
+        if backend.best_id(package).is_none() {
+            eprintln!("error: {} not found !", package);
+            failed = true;
+            continue;
+        }
 
-        let output = package.replace("/", "-");
-        _ = std::fs::write(output.clone() + ".dot", s.as_str());
+        match process_target(
+            target,
+            options,
+            backend,
+            &excludes,
+            &exporters,
+            &build_times,
+        ) {
+            Ok(mut target_renders) => renders.append(&mut target_renders),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                failed = true;
+            }
+        }
+    }
 
-        let graph_svg = exec_dot(s, vec![Format::Svg.into()]).unwrap();
-        _ = std::fs::write(output + ".svg", graph_svg);
+    if options.display {
+        for (output, dot) in &renders {
+            if let Err(err) = display(output, dot.clone(), options.cache) {
+                eprintln!("error: {}", err);
+                failed = true;
+            }
+        }
+    }
+    timed(Phase::Rendering, || render_all(renders, options));
+    !failed
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    };
+
+    if options.command == Command::Analyze {
+        if let Err(e) = analyze(&options) {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+        return;
+    }
+
+    let target_repositories: Vec<String> = if options.deps_from_repository {
+        options
+            .targets
+            .iter()
+            .filter_map(|t| t.repository.clone())
+            .collect()
     } else {
-        eprintln!("error: {} not found !", package);
+        Vec::new()
+    };
+
+    let succeeded = match options.backend {
+        Backend::Paludis => {
+            let mut e = match &options.root {
+                Some(root) => match Environment::with_root(root) {
+                    Ok(e) => e,
+                    Err(err) => {
+                        eprintln!("error: cannot open the environment of {}: {}", root, err);
+                        exit(1);
+                    }
+                },
+                None => Environment::default(),
+            };
+
+            // checkouts given on the command line hold the work in progress, they win over
+            // the configured repositories
+            let mut pinned = Vec::new();
+            for location in &options.add_repositories {
+                match e.add_repository(location) {
+                    Ok(name) => pinned.push(name),
+                    Err(err) => {
+                        eprintln!("error: cannot add repository {}: {}", location, err);
+                        exit(1);
+                    }
+                }
+            }
+            pinned.extend(target_repositories);
+            run(
+                &PaludisBackend::new(&e, pinned, options.prefer_installed, options.target.clone()),
+                &options,
+            )
+        }
+        Backend::Portage => {
+            // crossdev installs the packages built for a target under /usr/<target>, from the
+            // repositories of the build system
+            let root = Path::new(options.root.as_deref().unwrap_or("/"));
+            let installed_root = match &options.target {
+                Some(target) => root.join("usr").join(target),
+                None => root.to_path_buf(),
+            };
+            match PortageBackend::new(
+                root,
+                &installed_root,
+                &options.add_repositories,
+                target_repositories,
+                options.prefer_installed,
+            ) {
+                Ok(backend) => run(&backend, &options),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    exit(1);
+                }
+            }
+        }
+    };
+
+    if options.timings {
+        eprint!("{}", report());
+    }
+
+    if !succeeded {
         exit(1);
     }
 }
@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use paludis_rs::{Environment, PackageID, Repository};
+
+use crate::graph::is_account;
+use crate::timings::{timed, Phase};
+
+// nothing visible, fall back on the newest release, or the newest scm for live-only packages
+pub fn best_masked_id(mut ids: Vec<PackageID>) -> Option<PackageID> {
+    ids.sort_by_key(|id| (!id.version().is_scm(), id.version()));
+    ids.pop()
+}
+
+// graveyard holds the removed packages, unavailable the ones of repositories not synced
+const RETIRED: [&str; 3] = ["graveyard", "unavailable", "unavailable-unofficial"];
+
+// what is installed, and what has no packages to resolve to
+const SKIPPED: [&str; 6] = [
+    "installed",
+    "installed-accounts",
+    "installed_unpackaged",
+    "accounts",
+    "unwritten",
+    "repository",
+];
+
+// Resolves package names to the ID to traverse on demand: only the names met during the
+// traversal are ever looked up, instead of every package of every repository.
+pub struct Packages<'a> {
+    env: &'a Environment,
+    repositories: Vec<(String, Repository)>,
+    // never resolved to, only checked for what they still provide
+    retired: Vec<(String, Repository)>,
+    pinned: Vec<String>,
+    prefer_installed: bool,
+    // the multiarch target the installed packages have to be built for
+    target: Option<String>,
+    resolved: RefCell<HashMap<String, Option<Rc<PackageID>>>>,
+}
+
+impl<'a> Packages<'a> {
+    pub fn new(
+        env: &'a Environment,
+        pinned: Vec<String>,
+        prefer_installed: bool,
+        target: Option<String>,
+    ) -> Self {
+        timed(Phase::Repositories, || {
+            Self::open(env, pinned, prefer_installed, target)
+        })
+    }
+
+    fn open(
+        env: &'a Environment,
+        pinned: Vec<String>,
+        prefer_installed: bool,
+        target: Option<String>,
+    ) -> Self {
+        let mut repositories = env.repositories_names();
+        repositories.sort_by_key(|r| !pinned.contains(r));
+
+        let repositories = repositories
+            .into_iter()
+            .filter(|r| !SKIPPED.contains(&r.as_str()) && !RETIRED.contains(&r.as_str()))
+            .filter_map(|r| env.fetch_repository(&r).map(|repo| (r, repo)))
+            .collect();
+        let retired = RETIRED
+            .iter()
+            .filter_map(|r| env.fetch_repository(r).map(|repo| (r.to_string(), repo)))
+            .collect();
+
+        Packages {
+            env,
+            repositories,
+            retired,
+            pinned,
+            prefer_installed,
+            target,
+            resolved: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn resolve(&self, name: &str) -> Option<PackageID> {
+        if self.prefer_installed {
+            if let Some(id) = self.env.best_version(&self.installed_spec(name)) {
+                return Some(id);
+            }
+        }
+
+        // The first repository with a visible version wins, pinned repositories come first.
+        // Only the repositories kept here are asked, never the installed or retired ones.
+        let mut masked = None;
+        for (r, repo) in &self.repositories {
+            let ids = repo.package_ids(name);
+            if ids.is_empty() {
+                continue;
+            }
+
+            if let Some(id) = self.env.best_version(&format!("{}::{}", name, r)) {
+                return Some(id);
+            }
+            // the work in progress of a pinned repository wins, even masked
+            if self.pinned.contains(r) {
+                return best_masked_id(ids);
+            }
+            if masked.is_none() {
+                masked = best_masked_id(ids);
+            }
+        }
+
+        masked
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<PackageID>> {
+        if let Some(id) = self.resolved.borrow().get(name) {
+            return id.clone();
+        }
+
+        let id = timed(Phase::Repositories, || self.resolve(name)).map(Rc::new);
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_string(), id.clone());
+        id
+    }
+
+    // accounts are shared by every target
+    fn installed_spec(&self, name: &str) -> String {
+        match &self.target {
+            _ if is_account(name) => format!("{}::installed-accounts", name),
+            Some(target) => format!("{}::installed[targets:{}]", name, target),
+            None => format!("{}::installed", name),
+        }
+    }
+
+    pub fn installed(&self, name: &str) -> bool {
+        self.env.best_version(&self.installed_spec(name)).is_some()
+    }
+
+    pub fn retired_providers(&self, name: &str) -> Vec<String> {
+        self.retired
+            .iter()
+            .filter(|(_, repo)| !repo.package_ids(name).is_empty())
+            .map(|(r, _)| r.clone())
+            .collect()
+    }
+
+    pub fn insert(&self, name: &str, id: PackageID) {
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_string(), Some(Rc::new(id)));
+    }
+}
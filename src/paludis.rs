@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use paludis_rs::{DependencySpecTree, Environment, MetadataValue, PackageID};
+
+use crate::backend::{DepTree, PackageBackend};
+use crate::graph::PackageInfo;
+
+use crate::packages::{best_masked_id, Packages};
+
+// Exherbo platform of a target triple, as the platform: conditionals name it. These are not
+// the Gentoo keywords, 64-bit arm is armv8.
+fn platform(triple: &str) -> Option<&'static str> {
+    match triple.split('-').next()? {
+        "x86_64" => Some("amd64"),
+        "i386" | "i486" | "i586" | "i686" => Some("x86"),
+        "aarch64" => Some("armv8"),
+        arch if arch.starts_with("armv7") => Some("armv7"),
+        _ => None,
+    }
+}
+
+pub struct PaludisBackend<'a> {
+    env: &'a Environment,
+    packages: Packages<'a>,
+    target: Option<String>,
+}
+
+impl<'a> PaludisBackend<'a> {
+    pub fn new(
+        env: &'a Environment,
+        pinned: Vec<String>,
+        prefer_installed: bool,
+        target: Option<String>,
+    ) -> Self {
+        PaludisBackend {
+            env,
+            packages: Packages::new(env, pinned, prefer_installed, target.clone()),
+            target,
+        }
+    }
+
+    // The environment answers for the native target, the target and platform conditionals
+    // are answered for the one given instead.
+    fn target_condition(&self, condition: &str) -> Option<bool> {
+        let target = self.target.as_deref()?;
+        let (negated, flag) = match condition.strip_prefix('!') {
+            Some(flag) => (true, flag),
+            None => (false, condition),
+        };
+        let met = if let Some(t) = flag.strip_prefix("targets:") {
+            t == target
+        } else if let Some(p) = flag.strip_prefix("platform:") {
+            platform(target) == Some(p)
+        } else {
+            return None;
+        };
+        Some(met != negated)
+    }
+
+    fn convert(&self, id: &PackageID, tree: DependencySpecTree) -> Option<DepTree> {
+        match tree {
+            DependencySpecTree::None => None,
+            DependencySpecTree::NamedSet(_) => None,
+            DependencySpecTree::Labels(labels) => Some(DepTree::Labels(
+                labels.iter().map(|l| l.text().to_string()).collect(),
+            )),
+            DependencySpecTree::Package(p) => Some(DepTree::Package(p.full_name())),
+            DependencySpecTree::Conditional(condition, children) => {
+                let text = condition.text().trim_end_matches('?').to_string();
+                Some(DepTree::Conditional {
+                    met: self
+                        .target_condition(&text)
+                        .unwrap_or_else(|| condition.condition_met(self.env, id)),
+                    condition: text,
+                    children: self.convert_all(id, children),
+                })
+            }
+            DependencySpecTree::All(all) => Some(DepTree::All(self.convert_all(id, all))),
+        }
+    }
+
+    fn convert_all(&self, id: &PackageID, trees: Vec<DependencySpecTree>) -> Vec<DepTree> {
+        trees
+            .into_iter()
+            .filter_map(|t| self.convert(id, t))
+            .collect()
+    }
+}
+
+fn metadata_str(package: &PackageID, key: &str) -> Option<String> {
+    package
+        .metadata_key(key)
+        .map(|k| k.value_str())
+        .filter(|v| !v.is_empty())
+}
+
+impl PackageBackend for PaludisBackend<'_> {
+    type Id = PackageID;
+
+    fn best_id(&self, name: &str) -> Option<Rc<PackageID>> {
+        self.packages.get(name)
+    }
+
+    fn select(&self, name: &str, repository: &str) -> Result<(), String> {
+        let Some(repo) = self.env.fetch_repository(repository) else {
+            return Err(format!("repository {} not found !", repository));
+        };
+        let id = self
+            .env
+            .best_version(&format!("{}::{}", name, repository))
+            .or_else(|| best_masked_id(repo.package_ids(name)));
+        match id {
+            Some(id) => {
+                self.packages.insert(name, id);
+                Ok(())
+            }
+            None => Err(format!("{}::{} not found !", name, repository)),
+        }
+    }
+
+    fn name(&self, id: &PackageID) -> String {
+        id.name()
+    }
+
+    fn info(&self, package: &PackageID) -> PackageInfo {
+        PackageInfo {
+            version: Some(package.version().to_string()),
+            scm: package.version().is_scm(),
+            masked: package.masked(),
+            slot: metadata_str(package, "SLOT"),
+            repository: Some(package.repository_name()),
+            summary: metadata_str(package, "SUMMARY"),
+            homepage: metadata_str(package, "HOMEPAGE"),
+            licences: metadata_str(package, "LICENCES"),
+            maintainers: metadata_str(package, "BUGS_TO"),
+            platforms: metadata_str(package, "PLATFORMS"),
+            empty_dependencies: metadata_str(package, "DEPENDENCIES").is_none(),
+            build_time: None,
+            options: BTreeMap::new(),
+            // paludis-rs spec trees carry no blockers
+            blockers: Vec::new(),
+        }
+    }
+
+    fn dependencies(&self, id: &PackageID, key: &str) -> Option<DepTree> {
+        match id.metadata_key(key)?.value() {
+            MetadataValue::DependencySpecTree(d) => self.convert(id, d),
+            _ => None,
+        }
+    }
+
+    fn installed(&self, name: &str) -> bool {
+        self.packages.installed(name)
+    }
+
+    fn retired_providers(&self, name: &str) -> Vec<String> {
+        self.packages.retired_providers(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platforms_of_targets() {
+        for (triple, expected) in [
+            ("x86_64-pc-linux-gnu", Some("amd64")),
+            ("i686-pc-linux-musl", Some("x86")),
+            ("aarch64-unknown-linux-gnueabi", Some("armv8")),
+            ("armv7-unknown-linux-gnueabihf", Some("armv7")),
+            ("armv7a-unknown-linux-gnueabihf", Some("armv7")),
+            ("riscv64-unknown-linux-gnu", None),
+        ] {
+            assert_eq!(platform(triple), expected, "{}", triple);
+        }
+    }
+}
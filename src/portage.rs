@@ -0,0 +1,664 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::backend::{DepTree, PackageBackend};
+use crate::graph::PackageInfo;
+use crate::timings::{timed, Phase};
+
+// relative to the ROOT
+const REPOS_DIR: &str = "var/db/repos";
+const LEGACY_REPO: &str = "usr/portage";
+const VDB: &str = "var/db/pkg";
+
+pub struct PortageId {
+    name: String,
+    version: String,
+    repository: String,
+    metadata: HashMap<String, String>,
+    // USE flags the conditionals are evaluated with
+    enabled: HashSet<String>,
+}
+
+impl PortageId {
+    fn metadata(&self, key: &str) -> Option<String> {
+        self.metadata
+            .get(key)
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+fn is_version(version: &str) -> bool {
+    let (version, revision) = match version.rsplit_once("-r") {
+        Some((v, r)) if !r.is_empty() && r.bytes().all(|b| b.is_ascii_digit()) => (v, r),
+        _ => (version, "0"),
+    };
+    let mut parts = version.split('_');
+    let numbers = parts.next().unwrap_or("");
+    let numbers = numbers
+        .strip_suffix(|c: char| c.is_ascii_lowercase())
+        .unwrap_or(numbers);
+    !revision.is_empty()
+        && !numbers.is_empty()
+        && numbers
+            .split('.')
+            .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        && parts.all(|suffix| {
+            ["alpha", "beta", "pre", "rc", "p"].iter().any(|s| {
+                suffix
+                    .strip_prefix(s)
+                    .is_some_and(|n| n.bytes().all(|b| b.is_ascii_digit()))
+            })
+        })
+}
+
+// "cat/foo-bar-1.2-r1" -> ("cat/foo-bar", "1.2-r1")
+fn split_version(package: &str) -> Option<(&str, &str)> {
+    let mut dashes = package.match_indices('-').map(|(i, _)| i);
+    dashes.find_map(|i| is_version(&package[i + 1..]).then(|| (&package[..i], &package[i + 1..])))
+}
+
+fn is_live(version: &str) -> bool {
+    version.split(['.', '-', '_']).any(|p| p == "9999")
+}
+
+fn suffix_rank(suffix: &str) -> (u8, u64) {
+    for (rank, name) in ["alpha", "beta", "pre", "rc", "p"].iter().enumerate() {
+        if let Some(n) = suffix.strip_prefix(name) {
+            // no suffix sits between rc and p
+            let rank = if *name == "p" { 5 } else { rank as u8 };
+            return (rank, n.parse().unwrap_or(0));
+        }
+    }
+    (4, 0)
+}
+
+// numbers, letter, suffixes and revision
+type ParsedVersion = (Vec<u64>, Option<char>, Vec<(u8, u64)>, u64);
+
+// The PMS rules, without their string comparison of components with leading zeros.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn parse(version: &str) -> ParsedVersion {
+        let (version, revision) = match version.rsplit_once("-r") {
+            Some((v, r)) => (v, r.parse().unwrap_or(0)),
+            None => (version, 0),
+        };
+        let mut parts = version.split('_');
+        let numbers = parts.next().unwrap_or("");
+        let letter = numbers.chars().last().filter(|c| c.is_ascii_lowercase());
+        let numbers = numbers.trim_end_matches(|c: char| c.is_ascii_lowercase());
+        let mut suffixes: Vec<(u8, u64)> = parts.map(suffix_rank).collect();
+        // no suffix at all compares as between rc and p
+        suffixes.push((4, 0));
+        (
+            numbers.split('.').map(|n| n.parse().unwrap_or(0)).collect(),
+            letter,
+            suffixes,
+            revision,
+        )
+    }
+    parse(a).cmp(&parse(b))
+}
+
+// ">=dev-libs/foo-1.2:3/3.1=[bar,-baz]::gentoo" -> "dev-libs/foo"
+fn atom_name(atom: &str) -> Option<String> {
+    let versioned = atom.starts_with(['<', '>', '=', '~']);
+    let atom = atom.trim_start_matches(['<', '>', '=', '~']);
+    let atom = atom.split('[').next()?;
+    let atom = atom.split("::").next()?;
+    let atom = atom.split(':').next()?;
+    let atom = atom.trim_end_matches('*');
+    let name = if versioned {
+        split_version(atom)?.0
+    } else {
+        atom
+    };
+    name.contains('/').then(|| name.to_string())
+}
+
+fn parse_group(tokens: &[&str], pos: &mut usize, enabled: &HashSet<String>) -> Vec<DepTree> {
+    let mut trees = Vec::new();
+    while let Some(&token) = tokens.get(*pos) {
+        *pos += 1;
+        match token {
+            ")" => break,
+            "(" => trees.push(DepTree::All(parse_group(tokens, pos, enabled))),
+            // portage goes for the first alternative when none is installed
+            "||" => {
+                if tokens.get(*pos) == Some(&"(") {
+                    *pos += 1;
+                    let mut alternatives = parse_group(tokens, pos, enabled);
+                    alternatives.truncate(1);
+                    trees.push(DepTree::All(alternatives));
+                }
+            }
+            _ if token.ends_with('?') => {
+                let condition = token.trim_end_matches('?').to_string();
+                let flag = condition.trim_start_matches('!');
+                let met = enabled.contains(flag) != condition.starts_with('!');
+                let children = if tokens.get(*pos) == Some(&"(") {
+                    *pos += 1;
+                    parse_group(tokens, pos, enabled)
+                } else {
+                    Vec::new()
+                };
+                trees.push(DepTree::Conditional {
+                    condition,
+                    met,
+                    children,
+                });
+            }
+            // "!!a/b" is a hard blocker, both kinds block all the same here
+            _ if token.starts_with('!') => {
+                if let Some(name) = atom_name(token.trim_start_matches('!')) {
+                    trees.push(DepTree::Block(name));
+                }
+            }
+            _ => {
+                if let Some(name) = atom_name(token) {
+                    trees.push(DepTree::Package(name));
+                }
+            }
+        }
+    }
+    trees
+}
+
+fn parse_dependencies(spec: &str, enabled: &HashSet<String>) -> Vec<DepTree> {
+    let tokens: Vec<&str> = spec.split_whitespace().collect();
+    parse_group(&tokens, &mut 0, enabled)
+}
+
+fn read_md5_cache(path: &Path) -> HashMap<String, String> {
+    fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+// the metadata keys are in uppercase, the repository the package came from is not
+fn read_vdb_entry(path: &Path) -> HashMap<String, String> {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let key = entry.file_name().to_string_lossy().to_string();
+            (key == "repository" || key.bytes().all(|b| b.is_ascii_uppercase() || b == b'_'))
+                .then(|| Some((key, fs::read_to_string(entry.path()).ok()?)))
+                .flatten()
+        })
+        .collect()
+}
+
+fn repository_name(path: &Path) -> String {
+    fs::read_to_string(path.join("profiles/repo_name"))
+        .ok()
+        .map(|n| n.trim().to_string())
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| {
+            path.file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default()
+        })
+}
+
+// (version, entry) of every package-version file of `name` in `dir/category`
+fn versions_in(dir: &Path, name: &str) -> Vec<(String, PathBuf)> {
+    let Some((category, package)) = name.split_once('/') else {
+        return Vec::new();
+    };
+    fs::read_dir(dir.join(category))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let file = entry.file_name().to_string_lossy().to_string();
+            let (pn, version) = split_version(&file)?;
+            (pn == package).then(|| (version.to_string(), entry.path()))
+        })
+        .collect()
+}
+
+// newest release first, live ebuilds only when there is nothing else
+fn best_version(mut versions: Vec<(String, PathBuf)>) -> Option<(String, PathBuf)> {
+    versions.sort_by(|a, b| {
+        (!is_live(&a.0))
+            .cmp(&!is_live(&b.0))
+            .then(compare_versions(&a.0, &b.0))
+    });
+    versions.pop()
+}
+
+// Reads the metadata cache of the repositories and the installed package database directly.
+// USE flags are the IUSE defaults for available packages and the recorded ones for installed
+// packages, make.conf and package.use are not read, and neither are keywords and masks.
+pub struct PortageBackend {
+    repositories: Vec<(String, PathBuf)>,
+    vdb: PathBuf,
+    prefer_installed: bool,
+    resolved: RefCell<HashMap<String, Option<Rc<PortageId>>>>,
+}
+
+impl PortageBackend {
+    // the installed packages are read from `installed_root`, apart from `root` for crossdev
+    pub fn new(
+        root: &Path,
+        installed_root: &Path,
+        added: &[String],
+        pinned: Vec<String>,
+        prefer_installed: bool,
+    ) -> Result<Self, String> {
+        timed(Phase::Repositories, || {
+            Self::open(root, installed_root, added, pinned, prefer_installed)
+        })
+    }
+
+    fn open(
+        root: &Path,
+        installed_root: &Path,
+        added: &[String],
+        pinned: Vec<String>,
+        prefer_installed: bool,
+    ) -> Result<Self, String> {
+        let repos_dir = root.join(REPOS_DIR);
+        let mut configured: Vec<PathBuf> = fs::read_dir(&repos_dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| p.is_dir())
+            .collect();
+        if root.join(LEGACY_REPO).is_dir() {
+            configured.push(root.join(LEGACY_REPO));
+        }
+        configured.sort();
+
+        let mut repositories: Vec<(String, PathBuf)> = added
+            .iter()
+            .map(PathBuf::from)
+            .chain(configured)
+            .map(|p| (repository_name(&p), p))
+            .collect();
+        // overlays win over the main tree
+        let added = added.len();
+        repositories[added..].sort_by_key(|(name, _)| (!pinned.contains(name), name == "gentoo"));
+
+        if repositories.is_empty() {
+            return Err(format!(
+                "no portage repository found in {}",
+                repos_dir.display()
+            ));
+        }
+        Ok(PortageBackend {
+            repositories,
+            vdb: installed_root.join(VDB),
+            prefer_installed,
+            resolved: RefCell::new(HashMap::new()),
+        })
+    }
+
+    fn installed_id(&self, name: &str) -> Option<PortageId> {
+        let (version, path) = best_version(versions_in(&self.vdb, name))?;
+        let metadata = read_vdb_entry(&path);
+        let enabled = metadata
+            .get("USE")
+            .map(|u| u.split_whitespace().map(String::from).collect())
+            .unwrap_or_default();
+        Some(PortageId {
+            name: name.to_string(),
+            version,
+            repository: metadata
+                .get("repository")
+                .map(|r| r.trim().to_string())
+                .unwrap_or(String::from("installed")),
+            metadata,
+            enabled,
+        })
+    }
+
+    fn repository_id(&self, name: &str, repository: &str, path: &Path) -> Option<PortageId> {
+        let cache = path.join("metadata/md5-cache");
+        let (version, entry) = match best_version(versions_in(&cache, name)) {
+            Some(best) => best,
+            // without a cache there is only the ebuild name to go by, and no metadata
+            None => {
+                let ebuilds = fs::read_dir(path.join(name))
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter_map(|e| {
+                        let file = e.file_name().to_string_lossy().to_string();
+                        let pf = file.strip_suffix(".ebuild")?;
+                        let (_, version) = split_version(pf)?;
+                        Some((version.to_string(), e.path()))
+                    });
+                let (version, _) = best_version(ebuilds.collect())?;
+                (version, PathBuf::new())
+            }
+        };
+        let metadata = read_md5_cache(&entry);
+        let enabled = metadata
+            .get("IUSE")
+            .map(|iuse| {
+                iuse.split_whitespace()
+                    .filter_map(|f| f.strip_prefix('+'))
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Some(PortageId {
+            name: name.to_string(),
+            version,
+            repository: repository.to_string(),
+            metadata,
+            enabled,
+        })
+    }
+
+    fn resolve(&self, name: &str) -> Option<PortageId> {
+        if self.prefer_installed {
+            if let Some(id) = self.installed_id(name) {
+                return Some(id);
+            }
+        }
+        self.repositories
+            .iter()
+            .find_map(|(repository, path)| self.repository_id(name, repository, path))
+    }
+}
+
+impl PackageBackend for PortageBackend {
+    type Id = PortageId;
+
+    fn best_id(&self, name: &str) -> Option<Rc<PortageId>> {
+        if let Some(id) = self.resolved.borrow().get(name) {
+            return id.clone();
+        }
+
+        // the cache and VDB entries are read as the packages are resolved
+        let id = timed(Phase::Repositories, || self.resolve(name)).map(Rc::new);
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_string(), id.clone());
+        id
+    }
+
+    fn select(&self, name: &str, repository: &str) -> Result<(), String> {
+        let Some((_, path)) = self.repositories.iter().find(|(r, _)| r == repository) else {
+            return Err(format!("repository {} not found !", repository));
+        };
+        let id = timed(Phase::Repositories, || {
+            self.repository_id(name, repository, path)
+        })
+        .ok_or(format!("{}::{} not found !", name, repository))?;
+        self.resolved
+            .borrow_mut()
+            .insert(name.to_string(), Some(Rc::new(id)));
+        Ok(())
+    }
+
+    fn name(&self, id: &PortageId) -> String {
+        id.name.clone()
+    }
+
+    fn info(&self, id: &PortageId) -> PackageInfo {
+        PackageInfo {
+            version: Some(id.version.clone()),
+            scm: is_live(&id.version),
+            masked: false,
+            slot: id
+                .metadata("SLOT")
+                .map(|s| s.split('/').next().unwrap_or(&s).to_string()),
+            repository: Some(id.repository.clone()),
+            summary: id.metadata("DESCRIPTION"),
+            homepage: id.metadata("HOMEPAGE"),
+            licences: id.metadata("LICENSE"),
+            maintainers: None,
+            platforms: id.metadata("KEYWORDS"),
+            empty_dependencies: ["DEPEND", "BDEPEND", "RDEPEND", "PDEPEND"]
+                .iter()
+                .all(|k| id.metadata(k).is_none()),
+            build_time: None,
+            options: Default::default(),
+            blockers: Vec::new(),
+        }
+    }
+
+    // DEPENDENCIES is the Exherbo key, it maps to the Portage variables under the same labels
+    fn dependencies(&self, id: &PortageId, key: &str) -> Option<DepTree> {
+        if key != "DEPENDENCIES" {
+            return Some(DepTree::All(parse_dependencies(
+                &id.metadata(key)?,
+                &id.enabled,
+            )));
+        }
+
+        let mut all = Vec::new();
+        for (label, keys) in [
+            ("build", &["DEPEND", "BDEPEND"][..]),
+            ("run", &["RDEPEND"][..]),
+            ("post", &["PDEPEND"][..]),
+        ] {
+            all.push(DepTree::Labels(vec![label.to_string()]));
+            for key in keys {
+                if let Some(spec) = id.metadata(key) {
+                    all.extend(parse_dependencies(&spec, &id.enabled));
+                }
+            }
+        }
+        Some(DepTree::All(all))
+    }
+
+    fn installed(&self, name: &str) -> bool {
+        !versions_in(&self.vdb, name).is_empty()
+    }
+
+    // removed packages leave no trace in a portage repository
+    fn retired_providers(&self, _name: &str) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ordered(versions: &[&str]) {
+        for pair in versions.windows(2) {
+            assert_eq!(
+                compare_versions(pair[0], pair[1]),
+                Ordering::Less,
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+
+    #[test]
+    fn version_ordering() {
+        ordered(&[
+            "1.0_alpha",
+            "1.0_beta2",
+            "1.0_pre",
+            "1.0_rc1",
+            "1.0_rc2",
+            "1.0",
+            "1.0-r1",
+            "1.0-r10",
+            "1.0_p1",
+            "1.0a",
+            "1.0.1",
+            "1.9",
+            "1.10",
+        ]);
+        assert_eq!(compare_versions("1.0-r0", "1.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn live_versions_come_last() {
+        let version = |versions: &[&str]| {
+            best_version(
+                versions
+                    .iter()
+                    .map(|v| (v.to_string(), PathBuf::new()))
+                    .collect(),
+            )
+            .map(|(v, _)| v)
+        };
+        assert_eq!(version(&["9999", "2.0", "1.5"]).as_deref(), Some("2.0"));
+        assert_eq!(version(&["9999"]).as_deref(), Some("9999"));
+        assert_eq!(version(&[]), None);
+    }
+
+    #[test]
+    fn versions() {
+        assert!(is_version("1.2.3"));
+        assert!(is_version("1.2b_rc3_p1-r2"));
+        assert!(!is_version("100dpi-1.0"));
+        assert!(!is_version("1.2_foo"));
+        assert!(!is_version("1.2-r"));
+        assert_eq!(
+            split_version("font-adobe-100dpi-1.0.3-r1"),
+            Some(("font-adobe-100dpi", "1.0.3-r1"))
+        );
+        assert_eq!(split_version("dev-libs/foo-2"), Some(("dev-libs/foo", "2")));
+        assert_eq!(split_version("foo-bar"), None);
+    }
+
+    #[test]
+    fn atoms() {
+        for atom in [
+            "dev-libs/foo",
+            "dev-libs/foo:=",
+            "dev-libs/foo:3/3.1=",
+            ">=dev-libs/foo-1.2:3[bar,-baz(+)]::gentoo",
+            "=dev-libs/foo-1.2*",
+            "~dev-libs/foo-1.2",
+            "<dev-libs/foo-2_rc1-r1",
+        ] {
+            assert_eq!(atom_name(atom).as_deref(), Some("dev-libs/foo"), "{}", atom);
+        }
+        assert_eq!(
+            atom_name("dev-libs/foo-bar-baz").as_deref(),
+            Some("dev-libs/foo-bar-baz")
+        );
+        assert_eq!(atom_name("foo"), None);
+    }
+
+    // the packages and blockers of the branches taken
+    fn leaves<'t>(trees: &'t [DepTree], taken: &mut Vec<&'t DepTree>) {
+        for tree in trees {
+            match tree {
+                DepTree::Package(_) | DepTree::Block(_) => taken.push(tree),
+                DepTree::Labels(_) => {}
+                DepTree::Conditional { met, children, .. } => {
+                    if *met {
+                        leaves(children, taken);
+                    }
+                }
+                DepTree::All(all) => leaves(all, taken),
+            }
+        }
+    }
+
+    fn packages(trees: &[DepTree]) -> Vec<String> {
+        let mut taken = Vec::new();
+        leaves(trees, &mut taken);
+        taken
+            .into_iter()
+            .filter_map(|t| match t {
+                DepTree::Package(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn blockers(trees: &[DepTree]) -> Vec<String> {
+        let mut taken = Vec::new();
+        leaves(trees, &mut taken);
+        taken
+            .into_iter()
+            .filter_map(|t| match t {
+                DepTree::Block(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dependency_specs() {
+        let enabled = HashSet::from([String::from("gtk")]);
+        let trees = parse_dependencies(
+            "a/b || ( c/d e/f ) gtk? ( x/gtk !qt? ( x/noqt ) ) qt? ( x/qt ) !gtk? ( x/cli ) \
+             !blocked/pkg !!<strong/blocker-2 qt? ( !x/qtblock ) ( g/h )",
+            &enabled,
+        );
+        assert_eq!(packages(&trees), ["a/b", "c/d", "x/gtk", "x/noqt", "g/h"]);
+        assert_eq!(blockers(&trees), ["blocked/pkg", "strong/blocker"]);
+    }
+
+    #[test]
+    fn repository_and_vdb() {
+        let root = std::env::temp_dir().join(format!("eline-portage-{}", std::process::id()));
+        let repo = root.join("var/db/repos/gentoo");
+        let cache = repo.join("metadata/md5-cache/dev-libs");
+        let vdb = root.join("var/db/pkg/dev-libs/bar-1.0");
+        fs::create_dir_all(&cache).unwrap();
+        fs::create_dir_all(repo.join("profiles")).unwrap();
+        fs::create_dir_all(&vdb).unwrap();
+        fs::write(repo.join("profiles/repo_name"), "gentoo\n").unwrap();
+        fs::write(
+            cache.join("foo-1.0"),
+            "DEPEND=>=dev-libs/bar-1\nRDEPEND=ssl? ( dev-libs/openssl ) doc? ( app-doc/x )\n\
+             SLOT=0/1\nIUSE=+ssl doc\nDESCRIPTION=Foo\n",
+        )
+        .unwrap();
+        fs::write(cache.join("foo-1.1_rc1"), "SLOT=0\n").unwrap();
+        fs::write(cache.join("foo-9999"), "SLOT=0\n").unwrap();
+        fs::write(cache.join("foobar-2.0"), "SLOT=0\n").unwrap();
+        fs::write(vdb.join("SLOT"), "0\n").unwrap();
+        fs::write(vdb.join("repository"), "gentoo\n").unwrap();
+
+        let backend = PortageBackend::new(&root, &root, &[], Vec::new(), false).unwrap();
+        let foo = backend.best_id("dev-libs/foo").unwrap();
+        assert_eq!(foo.version, "1.1_rc1");
+        assert!(backend.installed("dev-libs/bar"));
+        assert!(!backend.installed("dev-libs/foo"));
+        let bar = backend.installed_id("dev-libs/bar").unwrap();
+        assert_eq!(bar.repository, "gentoo");
+
+        backend.resolved.borrow_mut().clear();
+        fs::remove_file(cache.join("foo-1.1_rc1")).unwrap();
+        let foo = backend.best_id("dev-libs/foo").unwrap();
+        let info = backend.info(&foo);
+        assert_eq!(info.version.as_deref(), Some("1.0"));
+        assert_eq!(info.slot.as_deref(), Some("0"));
+        assert_eq!(info.repository.as_deref(), Some("gentoo"));
+        assert_eq!(info.summary.as_deref(), Some("Foo"));
+        let Some(DepTree::All(dependencies)) = backend.dependencies(&foo, "DEPENDENCIES") else {
+            panic!("no dependencies");
+        };
+        assert_eq!(
+            packages(&dependencies),
+            ["dev-libs/bar", "dev-libs/openssl"]
+        );
+
+        // crossdev keeps the packages of a target apart, the repositories stay the same
+        let target = root.join("usr/armv7-unknown-linux-gnueabihf");
+        fs::create_dir_all(target.join("var/db/pkg/dev-libs/foo-1.0")).unwrap();
+        let cross = PortageBackend::new(&root, &target, &[], Vec::new(), false).unwrap();
+        assert!(cross.installed("dev-libs/foo"));
+        assert!(!cross.installed("dev-libs/bar"));
+        assert!(cross.best_id("dev-libs/foo").is_some());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+use crate::graph::{is_account, DepGraph};
+
+// (package, issue), sorted by package
+pub fn qa_issues(graph: &DepGraph) -> Vec<(String, String)> {
+    let mut issues = Vec::new();
+    for name in &graph.nodes {
+        let Some(info) = graph.info.get(name) else {
+            continue;
+        };
+        let mut flag = |issue: &str| issues.push((name.clone(), issue.to_string()));
+
+        match info.summary.as_deref().map(str::trim) {
+            None | Some("") => flag("empty SUMMARY"),
+            Some(summary) => {
+                let package = name.split('/').nth(1).unwrap_or(name);
+                if summary.eq_ignore_ascii_case(package) {
+                    flag("SUMMARY only repeats the package name");
+                }
+            }
+        }
+
+        match info.homepage.as_deref() {
+            None => flag("missing HOMEPAGE"),
+            Some(homepage) => {
+                if !homepage
+                    .split_whitespace()
+                    .all(|h| h.starts_with("https://") || h.starts_with("http://"))
+                {
+                    flag("HOMEPAGE is not an http(s) URL");
+                }
+            }
+        }
+
+        if info.licences.is_none() {
+            flag("missing LICENCES");
+        }
+
+        // virtuals and accounts are expected to have nothing else to pull in
+        if info.empty_dependencies && !name.starts_with("virtual/") && !is_account(name) {
+            flag("empty DEPENDENCIES");
+        }
+    }
+    issues
+}
@@ -0,0 +1,192 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread;
+
+use graphviz_rust::cmd::Format;
+use graphviz_rust::exec_dot;
+
+use crate::cli::Options;
+use crate::color::fnv1a;
+use crate::config::cache_dir;
+use crate::display::show;
+
+pub fn image_format(name: &str) -> Option<Format> {
+    match name {
+        "svg" => Some(Format::Svg),
+        "png" => Some(Format::Png),
+        "pdf" => Some(Format::Pdf),
+        "jpg" | "jpeg" => Some(Format::Jpg),
+        "gif" => Some(Format::Gif),
+        "webp" => Some(Format::Webp),
+        "ps" => Some(Format::Ps),
+        "eps" => Some(Format::Eps),
+        _ => None,
+    }
+}
+
+pub fn html_snippet(svg: &[u8]) -> String {
+    let svg = String::from_utf8_lossy(svg);
+    // the xml prolog and doctype are not welcome inside a page
+    let svg = svg.find("<svg").map_or(&svg[..], |start| &svg[start..]);
+    format!("<div class=\"eline-graph\">\n{}</div>\n", svg)
+}
+
+// The cache keeps every render next to the dot it comes from, a hash collision cannot
+// hand back the image of another graph.
+fn cached(dot: &str, format: Format) -> (Option<Vec<u8>>, Option<(PathBuf, PathBuf)>) {
+    let Some(dir) = cache_dir().map(|d| d.join("renders")) else {
+        return (None, None);
+    };
+    let extension = format!("{:?}", format).to_lowercase();
+    let key = format!("{:016x}", fnv1a(&format!("{}\n{}", extension, dot)));
+    let source = dir.join(format!("{}.dot", key));
+    let image = dir.join(format!("{}.{}", key, extension));
+
+    let hit = fs::read_to_string(&source)
+        .is_ok_and(|cached| cached == dot)
+        .then(|| fs::read(&image).ok())
+        .flatten();
+    (hit, Some((source, image)))
+}
+
+pub fn render(output: &str, dot: String, format: Format, cache: bool) -> Option<Vec<u8>> {
+    let (hit, entry) = if cache {
+        cached(&dot, format)
+    } else {
+        (None, None)
+    };
+    if hit.is_some() {
+        return hit;
+    }
+
+    match exec_dot(dot.clone(), vec![format.into()]) {
+        Ok(rendered) => {
+            if let Some((source, image)) = entry {
+                // a cache that cannot be written is only a slower run
+                if source
+                    .parent()
+                    .is_some_and(|d| fs::create_dir_all(d).is_ok())
+                {
+                    _ = fs::write(&image, &rendered).and_then(|_| fs::write(&source, &dot));
+                }
+            }
+            Some(rendered)
+        }
+        Err(e) => {
+            eprintln!("error: could not render {}: {}", output, e);
+            None
+        }
+    }
+}
+
+// graphviz is by far the slowest step on big graphs, and each graph renders on its own
+pub fn render_all(renders: Vec<(String, String)>, options: &Options) {
+    let Some(image) = image_format(&options.format) else {
+        return;
+    };
+    let queue = Mutex::new(renders.into_iter());
+
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.max(1) {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let Some((output, dot)) = next else {
+                    break;
+                };
+
+                let mut svg = None;
+                if options.image {
+                    let path = format!("{}.{}", output, options.format);
+                    if let Some(rendered) = render(&path, dot.clone(), image, options.cache) {
+                        _ = fs::write(&path, &rendered);
+                        if options.format == "svg" {
+                            svg = Some(rendered);
+                        }
+                    }
+                }
+
+                if options.html {
+                    let path = output + ".html";
+                    if let Some(svg) =
+                        svg.or_else(|| render(&path, dot, Format::Svg, options.cache))
+                    {
+                        _ = fs::write(path, html_snippet(&svg));
+                    }
+                }
+            });
+        }
+    });
+}
+
+// a file of this run only, named after the graph it belongs to
+pub fn temporary(output: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "eline-{}-{:016x}.{}",
+        std::process::id(),
+        fnv1a(output),
+        extension
+    ))
+}
+
+// graphviz reads the document from `source` itself, it never has to be in memory
+fn render_file(source: &Path, format: &str, to: &Path) -> bool {
+    let status = Command::new("dot")
+        .arg(format!("-T{}", format))
+        .arg("-o")
+        .arg(to)
+        .arg(source)
+        .status();
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!(
+                "error: could not render {}: dot failed: {}",
+                to.display(),
+                status
+            );
+            false
+        }
+        Err(e) => {
+            eprintln!("error: could not render {}: {}", to.display(), e);
+            false
+        }
+    }
+}
+
+// What render_all and display do, for a document written by --stream. It skips the cache,
+// which would need the document in memory to be keyed.
+pub fn render_streamed(source: &Path, output: &str, options: &Options) -> Result<(), String> {
+    let image = PathBuf::from(format!("{}.{}", output, options.format));
+    let rendered = options.image && render_file(source, &options.format, &image);
+
+    if options.html {
+        let (svg, temporary_svg) = if rendered && options.format == "svg" {
+            (image, false)
+        } else {
+            (temporary(output, "svg"), true)
+        };
+        if !temporary_svg || render_file(source, "svg", &svg) {
+            if let Ok(content) = fs::read(&svg) {
+                _ = fs::write(output.to_string() + ".html", html_snippet(&content));
+            }
+        }
+        if temporary_svg {
+            _ = fs::remove_file(&svg);
+        }
+    }
+
+    if options.display {
+        let png = temporary(output, "png");
+        let content = render_file(source, "png", &png)
+            .then(|| fs::read(&png).ok())
+            .flatten();
+        _ = fs::remove_file(&png);
+        match content {
+            Some(content) => show(output, &content)?,
+            None => return Err(format!("could not render {}", output)),
+        }
+    }
+    Ok(())
+}
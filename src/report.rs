@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+
+use eline::analysis::{critical_path, cycles};
+use eline::graph::DepGraph;
+
+use crate::licence::{classify, LicenceClass};
+use crate::render::html_snippet;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn parse(value: &str) -> Option<ReportFormat> {
+        match value {
+            "markdown" | "md" => Some(ReportFormat::Markdown),
+            "html" => Some(ReportFormat::Html),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Section {
+    title: String,
+    text: Vec<String>,
+    items: Vec<String>,
+}
+
+fn section(title: &str) -> Section {
+    Section {
+        title: title.to_string(),
+        ..Section::default()
+    }
+}
+
+fn escape_html(content: &str) -> String {
+    content
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn sections(
+    graph: &DepGraph,
+    missing: &[&String],
+    installed: &dyn Fn(&str) -> bool,
+) -> Vec<Section> {
+    let mut sections = Vec::new();
+
+    let mut dependencies = section("Dependencies");
+    let direct = graph
+        .adjacency()
+        .get(graph.root.as_str())
+        .map_or(0, Vec::len);
+    dependencies.items.push(format!(
+        "{} dependencies, {} of them direct",
+        graph.nodes.len().saturating_sub(1),
+        direct
+    ));
+    let post = graph.edges.iter().filter(|e| e.post).count();
+    if post > 0 {
+        dependencies
+            .items
+            .push(format!("{} post-dependency edges", post));
+    }
+    let path = critical_path(graph);
+    dependencies.items.push(format!(
+        "longest dependency chain ({} packages): {}",
+        path.len(),
+        path.join(" -> ")
+    ));
+    sections.push(dependencies);
+
+    let mut new = section("New packages");
+    new.text.push(format!(
+        "{} of {} packages are not installed yet.",
+        missing.len(),
+        graph.nodes.len()
+    ));
+    new.items = missing.iter().map(|p| p.to_string()).collect();
+    sections.push(new);
+
+    let mut licences = section("Licences");
+    let mut classes: BTreeMap<LicenceClass, Vec<&str>> = BTreeMap::new();
+    for name in &graph.nodes {
+        let class = classify(
+            graph
+                .info
+                .get(name)
+                .and_then(|i| i.licences.as_deref())
+                .unwrap_or(""),
+        );
+        classes.entry(class).or_default().push(name);
+    }
+    for (class, packages) in &classes {
+        // the permissive and copyleft ones need no second look
+        let item = match class {
+            LicenceClass::Permissive | LicenceClass::Copyleft => {
+                format!("{}: {} packages", class.name(), packages.len())
+            }
+            _ => format!(
+                "{}: {} packages ({})",
+                class.name(),
+                packages.len(),
+                packages.join(", ")
+            ),
+        };
+        licences.items.push(item);
+    }
+    sections.push(licences);
+
+    let mut findings = section("Findings");
+    for cycle in cycles(graph) {
+        findings
+            .items
+            .push(format!("dependency cycle: {}", cycle.join(", ")));
+    }
+    for name in &graph.nodes {
+        if graph.info.get(name).is_some_and(|i| i.masked) {
+            findings
+                .items
+                .push(format!("{} only resolves to a masked version", name));
+        }
+        // a blocker only matters when the blocked package is wanted or already there
+        for blocked in graph.info.get(name).map_or(&[][..], |i| &i.blockers) {
+            if graph.info.contains_key(blocked) {
+                findings.items.push(format!(
+                    "{} blocks {}, both are in the dependencies",
+                    name, blocked
+                ));
+            } else if installed(blocked) {
+                findings
+                    .items
+                    .push(format!("{} blocks {}, which is installed", name, blocked));
+            }
+        }
+    }
+    for (name, repositories) in &graph.retired {
+        findings.items.push(format!(
+            "{} is only in {}, needed by {}",
+            name,
+            repositories.join(", "),
+            graph.dependents(name).join(", ")
+        ));
+    }
+    if findings.items.is_empty() {
+        findings.text.push(String::from("Nothing notable."));
+    }
+    sections.push(findings);
+
+    sections
+}
+
+// `image` is the rendered svg file, linked from markdown and inlined in html
+pub fn write_report(
+    graph: &DepGraph,
+    missing: &[&String],
+    installed: &dyn Fn(&str) -> bool,
+    image: Option<(&str, &[u8])>,
+    format: ReportFormat,
+) -> String {
+    let title = format!("Dependency report for {}", graph.root);
+    let sections = sections(graph, missing, installed);
+
+    let mut out = String::new();
+    match format {
+        ReportFormat::Markdown => {
+            out += &format!("# {}\n", title);
+            for (i, section) in sections.iter().enumerate() {
+                out += &format!("\n## {}\n\n", section.title);
+                for text in &section.text {
+                    out += &format!("{}\n\n", text);
+                }
+                for item in &section.items {
+                    out += &format!("- {}\n", item);
+                }
+                if i == 0 {
+                    if let Some((file, _)) = image {
+                        out += &format!("\n![dependency graph]({})\n", file);
+                    }
+                }
+            }
+        }
+        ReportFormat::Html => {
+            out += &format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+                 </head>\n<body>\n<h1>{}</h1>\n",
+                escape_html(&title),
+                escape_html(&title)
+            );
+            for (i, section) in sections.iter().enumerate() {
+                out += &format!("<h2>{}</h2>\n", escape_html(&section.title));
+                for text in &section.text {
+                    out += &format!("<p>{}</p>\n", escape_html(text));
+                }
+                if !section.items.is_empty() {
+                    out += "<ul>\n";
+                    for item in &section.items {
+                        out += &format!("<li>{}</li>\n", escape_html(item));
+                    }
+                    out += "</ul>\n";
+                }
+                if i == 0 {
+                    if let Some((_, svg)) = image {
+                        out += &html_snippet(svg);
+                    }
+                }
+            }
+            out += "</body>\n</html>\n";
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use eline::graph::{DepEdge, PackageInfo};
+
+    use super::*;
+
+    #[test]
+    fn findings() {
+        let blocking = PackageInfo {
+            blockers: vec![
+                String::from("b/needed"),
+                String::from("c/installed"),
+                String::from("d/absent"),
+            ],
+            ..Default::default()
+        };
+        let graph = DepGraph {
+            root: String::from("a/root"),
+            nodes: vec![String::from("a/root"), String::from("b/needed")],
+            edges: ["b/needed", "e/gone"]
+                .iter()
+                .map(|to| DepEdge {
+                    from: String::from("a/root"),
+                    to: to.to_string(),
+                    post: false,
+                    conditions: Vec::new(),
+                })
+                .collect(),
+            info: HashMap::from([
+                (String::from("a/root"), blocking),
+                (String::from("b/needed"), PackageInfo::default()),
+            ]),
+            retired: BTreeMap::from([(String::from("e/gone"), vec![String::from("graveyard")])]),
+        };
+        let report = write_report(
+            &graph,
+            &[],
+            &|name| name == "c/installed",
+            None,
+            ReportFormat::Markdown,
+        );
+        assert!(report.contains("- a/root blocks b/needed, both are in the dependencies\n"));
+        assert!(report.contains("- a/root blocks c/installed, which is installed\n"));
+        assert!(!report.contains("d/absent"));
+        assert!(report.contains("- e/gone is only in graveyard, needed by a/root\n"));
+    }
+}
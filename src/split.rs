@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+use graphviz_rust::dot_structures::{Attribute, Graph, Id, Node, NodeId, Stmt};
+
+use eline::analysis::communities;
+
+use crate::graph::DepGraph;
+use crate::{attribute, dot_graph, edge_maker, graphiz_escape, DotStyle};
+
+#[derive(Clone, Copy)]
+pub enum SplitBy {
+    Category,
+    Repository,
+    Maintainer,
+    Platforms,
+    Community,
+}
+
+impl SplitBy {
+    pub fn parse(value: &str) -> Option<SplitBy> {
+        match value {
+            "category" => Some(SplitBy::Category),
+            "repository" => Some(SplitBy::Repository),
+            "maintainer" => Some(SplitBy::Maintainer),
+            "platforms" => Some(SplitBy::Platforms),
+            "community" => Some(SplitBy::Community),
+            _ => None,
+        }
+    }
+}
+
+pub fn cluster_of(
+    graph: &DepGraph,
+    package: &str,
+    by: SplitBy,
+    communities: &HashMap<String, String>,
+) -> String {
+    let info = graph.info.get(package);
+    let key = match by {
+        SplitBy::Category => Some(package.split('/').next().unwrap_or(package).to_string()),
+        SplitBy::Repository => info.and_then(|i| i.repository.clone()),
+        SplitBy::Maintainer => info.and_then(|i| i.maintainer()),
+        SplitBy::Platforms => info.and_then(|i| i.platforms_key()),
+        // the root is in no community, it gets its own cluster
+        SplitBy::Community => Some(
+            communities
+                .get(package)
+                .cloned()
+                .unwrap_or(package.to_string()),
+        ),
+    };
+    key.unwrap_or(String::from(if info.is_some() {
+        "unknown"
+    } else {
+        "unresolved"
+    }))
+}
+
+fn cluster_node(id: &str, attributes: Vec<Attribute>) -> Stmt {
+    Stmt::Node(Node {
+        id: NodeId(Id::Plain(graphiz_escape(id)), None),
+        attributes,
+    })
+}
+
+// One overview graph of the clusters, linking to one graph per cluster. Packages of
+// other clusters a cluster depends on are kept in its graph, greyed out.
+pub fn split_graph(
+    graph: &DepGraph,
+    by: SplitBy,
+    output: &str,
+    extension: &str,
+    style: &DotStyle,
+) -> (Graph, Vec<(String, Graph)>) {
+    let communities = match by {
+        SplitBy::Community => communities(graph),
+        _ => HashMap::new(),
+    };
+    let mut clusters: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for package in graph.packages() {
+        clusters
+            .entry(cluster_of(graph, package, by, &communities))
+            .or_default()
+            .push(package);
+    }
+
+    let mut links: BTreeMap<(String, String), usize> = BTreeMap::new();
+    for e in &graph.edges {
+        let from = cluster_of(graph, &e.from, by, &communities);
+        let to = cluster_of(graph, &e.to, by, &communities);
+        if from != to {
+            *links.entry((from, to)).or_default() += 1;
+        }
+    }
+
+    let base = Path::new(output)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or(output.to_string());
+
+    let mut overview = Vec::new();
+    for (cluster, members) in &clusters {
+        overview.push(cluster_node(
+            cluster,
+            vec![
+                attribute("shape", "box"),
+                attribute(
+                    "label",
+                    &format!("{}\\n{} packages", cluster, members.len()),
+                ),
+                attribute(
+                    "URL",
+                    &format!("{}-{}.{}", base, cluster.replace('/', "-"), extension),
+                ),
+            ],
+        ));
+    }
+    for ((from, to), count) in &links {
+        let mut edge = edge_maker(from.clone(), to.clone());
+        if let Stmt::Edge(ref mut edge) = edge {
+            edge.attributes.push(attribute("label", &count.to_string()));
+        }
+        overview.push(edge);
+    }
+
+    let mut parts = Vec::new();
+    for (cluster, members) in &clusters {
+        let members: HashSet<&str> = members.iter().copied().collect();
+        let mut part = DepGraph {
+            root: cluster.clone(),
+            nodes: graph
+                .nodes
+                .iter()
+                .filter(|n| members.contains(n.as_str()))
+                .cloned()
+                .collect(),
+            edges: graph
+                .edges
+                .iter()
+                .filter(|e| members.contains(e.from.as_str()))
+                .cloned()
+                .collect(),
+            info: graph
+                .info
+                .iter()
+                .filter(|(n, _)| members.contains(n.as_str()))
+                .map(|(n, i)| (n.clone(), i.clone()))
+                .collect(),
+            retired: BTreeMap::new(),
+        };
+        part.keep_retired(graph);
+
+        let outside: HashSet<&str> = part
+            .edges
+            .iter()
+            .map(|e| e.to.as_str())
+            // the retired ones are drawn as such wherever they are
+            .filter(|to| !members.contains(to) && !part.retired.contains_key(*to))
+            .collect();
+
+        let mut dot = dot_graph(&part, style);
+        if let Graph::Graph { ref mut stmts, .. } = dot {
+            let mut outside: Vec<&str> = outside.into_iter().collect();
+            outside.sort();
+            for package in outside {
+                stmts.push(cluster_node(
+                    package,
+                    vec![attribute("color", "grey"), attribute("fontcolor", "grey")],
+                ));
+            }
+        }
+        // communities are named after a package, their name holds a '/'
+        parts.push((format!("{}-{}", output, cluster.replace('/', "-")), dot));
+    }
+
+    let overview = Graph::Graph {
+        id: Id::Plain(graphiz_escape(&graph.root)),
+        strict: false,
+        stmts: overview,
+    };
+    (overview, parts)
+}
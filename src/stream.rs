@@ -0,0 +1,30 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use graphviz_rust::printer::{DotPrinter, PrinterContext};
+
+use crate::graph::DepGraph;
+use crate::{dot_statements, graphiz_escape, DotStyle};
+
+// Writes the same document as printing dot_graph, without ever holding more than one
+// statement of it in memory.
+pub fn write_dot<W: Write>(graph: &DepGraph, style: &DotStyle, mut out: W) -> io::Result<()> {
+    writeln!(out, "graph {} {{", graphiz_escape(&graph.root))?;
+
+    let mut ctx = PrinterContext::default();
+    let mut res = Ok(());
+    dot_statements(graph, style, &mut |stmt| {
+        if res.is_ok() {
+            res = writeln!(out, "  {};", stmt.print(&mut ctx));
+        }
+    });
+    res?;
+
+    writeln!(out, "}}")?;
+    out.flush()
+}
+
+pub fn write_dot_file(graph: &DepGraph, style: &DotStyle, path: &Path) -> io::Result<()> {
+    write_dot(graph, style, BufWriter::new(File::create(path)?))
+}
@@ -0,0 +1,57 @@
+use std::cell::Cell;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub enum Phase {
+    Repositories,
+    Metadata,
+    Folding,
+    Printing,
+    Rendering,
+}
+
+const PHASES: [(Phase, &str); 5] = [
+    (Phase::Repositories, "repository scanning"),
+    (Phase::Metadata, "metadata fetching"),
+    (Phase::Folding, "graph folding"),
+    (Phase::Printing, "dot printing"),
+    (Phase::Rendering, "graphviz rendering"),
+];
+
+static SPENT: Mutex<[Duration; 5]> = Mutex::new([Duration::ZERO; 5]);
+
+thread_local! {
+    // time spent in the phases nested in the current one, not to be counted twice
+    static NESTED: Cell<Duration> = const { Cell::new(Duration::ZERO) };
+}
+
+pub fn timed<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let outer = NESTED.replace(Duration::ZERO);
+    let start = Instant::now();
+    let res = f();
+    let elapsed = start.elapsed();
+
+    let own = elapsed.saturating_sub(NESTED.get());
+    SPENT.lock().unwrap()[phase as usize] += own;
+    NESTED.set(outer + elapsed);
+    res
+}
+
+pub fn report() -> String {
+    let spent = SPENT.lock().unwrap();
+    let mut out = String::from("timings:\n");
+    for (phase, name) in PHASES {
+        out += &format!(
+            "  {:<20} {:>9.3}s\n",
+            name,
+            spent[phase as usize].as_secs_f64()
+        );
+    }
+    out += &format!(
+        "  {:<20} {:>9.3}s\n",
+        "total",
+        spent.iter().sum::<Duration>().as_secs_f64()
+    );
+    out
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use std::ops::ControlFlow;
+
+use crate::backend::{DepTree, PackageBackend};
+use crate::graph::{is_account, DepEdge};
+
+pub struct LabelFilter {
+    pub deny: Vec<String>,
+    pub allow: Option<Vec<String>>,
+}
+
+fn authorized_labels(labels: &[String], filter: &LabelFilter) -> bool {
+    // build+run: may come as one label or as two
+    let labels = labels
+        .iter()
+        .flat_map(|l| l.split('+').map(String::from).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    !labels.iter().any(|l| filter.deny.contains(l))
+        && filter
+            .allow
+            .as_ref()
+            .is_none_or(|allow| labels.iter().any(|l| allow.contains(l)))
+}
+
+fn is_post(labels: &[String]) -> bool {
+    !labels.is_empty() && labels.iter().all(|l| l == "post")
+}
+
+// keeps the specs the filter authorizes, each with whether it is a post-dependency
+fn clean_deps(deps: Vec<DepTree>, filter: &LabelFilter, mut post: bool) -> Vec<(DepTree, bool)> {
+    let mut res = Vec::new();
+    let mut skip = false;
+
+    deps.into_iter().for_each(|d: DepTree| {
+        if skip {
+            if let DepTree::Labels(labels) = d {
+                if authorized_labels(&labels, filter) {
+                    skip = false;
+                    post = is_post(&labels);
+                    res.push((DepTree::Labels(labels), post));
+                }
+            }
+        } else {
+            if let DepTree::Labels(labels) = d {
+                if !authorized_labels(&labels, filter) {
+                    skip = true;
+                } else {
+                    post = is_post(&labels);
+                    res.push((DepTree::Labels(labels), post));
+                }
+            } else {
+                res.push((d, post));
+            }
+        }
+    });
+
+    res
+}
+
+pub struct Traversal<'a, B: PackageBackend> {
+    pub backend: &'a B,
+    pub depth_max: usize,
+    pub defer_post: bool,
+    pub include_accounts: bool,
+    pub show_conditionals: bool,
+    // the metadata key holding the dependency spec tree to follow
+    pub key: &'a str,
+    pub exclude: &'a dyn Fn(&str) -> bool,
+    pub labels: &'a LabelFilter,
+}
+
+// What the traversal comes across, in the order it does. The edge to a package always comes
+// before the package itself, which only comes once, the first time it is reached.
+pub enum Event<'e, Id> {
+    Node {
+        name: &'e str,
+        id: &'e Id,
+        depth: usize,
+    },
+    Edge(&'e DepEdge),
+    // an option a conditional of `package` was evaluated with
+    Choice {
+        package: &'e str,
+        option: &'e str,
+        enabled: bool,
+    },
+    // `package` cannot be installed alongside `blocked`
+    Block {
+        package: &'e str,
+        blocked: &'e str,
+    },
+}
+
+struct Walk<'t, 'a, B: PackageBackend, F> {
+    traversal: &'t Traversal<'a, B>,
+    callback: F,
+    mark: HashSet<String>,
+}
+
+impl<B: PackageBackend, F: FnMut(Event<B::Id>) -> ControlFlow<()>> Walk<'_, '_, B, F> {
+    fn spec(
+        &mut self,
+        package: &str,
+        pkg_dep: DepTree,
+        post: bool,
+        conditions: &[String],
+        // false in the branches only walked for --show-conditionals
+        met: bool,
+        depth: usize,
+    ) -> ControlFlow<()> {
+        let traversal = self.traversal;
+        match pkg_dep {
+            DepTree::Labels(_) => {}
+            DepTree::Block(blocked) => {
+                if met {
+                    (self.callback)(Event::Block {
+                        package,
+                        blocked: &blocked,
+                    })?;
+                }
+            }
+            DepTree::Package(name) => {
+                if (traversal.include_accounts || !is_account(&name)) && !(traversal.exclude)(&name)
+                {
+                    // a deferred post-dependency is not needed before the package itself,
+                    // it lives at the same depth
+                    let depth = if post && traversal.defer_post {
+                        depth - 1
+                    } else {
+                        depth
+                    };
+
+                    let pid = traversal.backend.best_id(&name);
+                    (self.callback)(Event::Edge(&DepEdge {
+                        from: package.to_string(),
+                        to: name,
+                        post,
+                        conditions: conditions.to_vec(),
+                    }))?;
+
+                    if let Some(pid) = pid {
+                        self.package(&pid, depth)?;
+                    }
+                }
+            }
+            DepTree::Conditional {
+                condition,
+                met: condition_met,
+                children,
+            } => {
+                let (option, enabled) = match condition.strip_prefix('!') {
+                    Some(option) => (option, !condition_met),
+                    None => (condition.as_str(), condition_met),
+                };
+                (self.callback)(Event::Choice {
+                    package,
+                    option,
+                    enabled,
+                })?;
+
+                if condition_met || traversal.show_conditionals {
+                    let mut conditions = conditions.to_vec();
+                    if traversal.show_conditionals {
+                        conditions.push(condition.clone());
+                    }
+                    for (c, post) in clean_deps(children, traversal.labels, post) {
+                        self.spec(package, c, post, &conditions, met && condition_met, depth)?;
+                    }
+                }
+            }
+            DepTree::All(all) => {
+                for (a, post) in clean_deps(all, traversal.labels, post) {
+                    self.spec(package, a, post, conditions, met, depth)?;
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn package(&mut self, package: &B::Id, depth: usize) -> ControlFlow<()> {
+        let traversal = self.traversal;
+        let name = traversal.backend.name(package);
+        if !self.mark.insert(name.clone()) {
+            return ControlFlow::Continue(());
+        }
+
+        (self.callback)(Event::Node {
+            name: &name,
+            id: package,
+            depth,
+        })?;
+
+        if depth == traversal.depth_max {
+            return ControlFlow::Continue(());
+        }
+
+        match traversal.backend.dependencies(package, traversal.key) {
+            Some(d) => self.spec(&name, d, false, &[], true, depth + 1),
+            None => ControlFlow::Continue(()),
+        }
+    }
+}
+
+// Walks the dependencies of `root` depth first, handing every event to `callback` as it
+// happens. The walk stops as soon as the callback breaks, and tells whether it did.
+pub fn traverse<B: PackageBackend>(
+    traversal: &Traversal<B>,
+    root: &B::Id,
+    callback: impl FnMut(Event<B::Id>) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut walk = Walk {
+        traversal,
+        callback,
+        mark: HashSet::new(),
+    };
+    walk.package(root, 0)
+}
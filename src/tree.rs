@@ -0,0 +1,111 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::in_degrees;
+use crate::graph::DepGraph;
+
+fn render_node<'a>(
+    package: &'a str,
+    prefix: &str,
+    adjacency: &HashMap<&'a str, Vec<&'a str>>,
+    occurrences: &HashMap<&str, usize>,
+    seen_counts: bool,
+    expanded: &mut HashSet<&'a str>,
+    out: &mut String,
+) {
+    let children = adjacency.get(package).map(Vec::as_slice).unwrap_or(&[]);
+    for (i, &child) in children.iter().enumerate() {
+        let last = i == children.len() - 1;
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        out.push_str(child);
+
+        let repeated = !expanded.insert(child);
+        if repeated {
+            out.push_str(" (*)");
+        }
+        let count = occurrences.get(child).copied().unwrap_or(0);
+        if seen_counts && count > 1 {
+            out.push_str(&format!(" (seen {} times)", count));
+        }
+        out.push('\n');
+
+        if !repeated {
+            let prefix = prefix.to_string() + if last { "    " } else { "│   " };
+            render_node(
+                child,
+                &prefix,
+                adjacency,
+                occurrences,
+                seen_counts,
+                expanded,
+                out,
+            );
+        }
+    }
+}
+
+// what in the graph depends on `package`, up to the root, the same way render_tree goes down
+pub fn render_reverse_tree(graph: &DepGraph, package: &str) -> String {
+    let reverse = graph.reverse_adjacency();
+
+    let mut out = package.to_string() + "\n";
+    let mut expanded = HashSet::from([package]);
+    render_node(
+        package,
+        "",
+        &reverse,
+        &HashMap::new(),
+        false,
+        &mut expanded,
+        &mut out,
+    );
+    out
+}
+
+pub fn render_tree(graph: &DepGraph, seen_counts: bool) -> String {
+    let adjacency = graph.adjacency();
+    let occurrences = in_degrees(graph);
+
+    let mut out = graph.root.clone() + "\n";
+    let mut expanded = HashSet::from([graph.root.as_str()]);
+    render_node(
+        &graph.root,
+        "",
+        &adjacency,
+        &occurrences,
+        seen_counts,
+        &mut expanded,
+        &mut out,
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::DepEdge;
+
+    #[test]
+    fn reverse_tree_goes_up_to_the_root() {
+        let edges = [("r", "a"), ("r", "b"), ("a", "z"), ("b", "z"), ("b", "a")];
+        let graph = DepGraph {
+            root: String::from("r"),
+            nodes: ["r", "a", "b", "z"].map(String::from).to_vec(),
+            edges: edges
+                .iter()
+                .map(|(from, to)| DepEdge {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    post: false,
+                    conditions: Vec::new(),
+                })
+                .collect(),
+            info: HashMap::new(),
+            retired: Default::default(),
+        };
+        assert_eq!(
+            render_reverse_tree(&graph, "z"),
+            "z\n├── a\n│   ├── r\n│   └── b\n│       └── r (*)\n└── b (*)\n"
+        );
+    }
+}